@@ -0,0 +1,188 @@
+use raytracing::camera::*;
+use raytracing::color::*;
+use raytracing::material::*;
+use raytracing::random::*;
+use raytracing::ray::*;
+use raytracing::scene::*;
+use raytracing::sphere::*;
+use raytracing::vec3::*;
+
+use std::hint::black_box;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const SAMPLES: usize = 10; // Timed runs of each benchmark, after one untimed warm-up run
+
+fn main() {
+    // Run with `cargo bench`, optionally followed by `--` and the names of the benchmarks to run,
+    // e.g. `cargo bench -- order`; all of them are run otherwise. Cargo passes flags like
+    // `--bench` too, which aren't names.
+    let benches: &[(&str, fn())] = &[
+        ("render", render),
+        ("hit", hit),
+        ("sphere", sphere),
+        ("passes", passes),
+        ("4k", four_k),
+        ("order", order),
+    ];
+
+    let names: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-'))
+        .collect();
+    for (name, bench) in benches {
+        if names.is_empty() || names.iter().any(|n| n == name) {
+            bench();
+        }
+    }
+}
+
+fn measure<T, F: FnMut() -> T>(label: &str, mut f: F) -> Duration {
+    // Shows the median and fastest of the timed runs of `f`, returning the median.
+    black_box(f());
+    let mut times: Vec<Duration> = (0..SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(f());
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+
+    let median = times[SAMPLES / 2];
+    println!(
+        "{label:<40} median {median:>10.2?}  fastest {:>10.2?}",
+        times[0]
+    );
+    median
+}
+
+fn render() {
+    // The render loop on the demo scene, on one thread so other threads don't add noise.
+    let (scene, options) = random_spheres(&mut Rng::new(0));
+    let options = CameraOptions {
+        image_width: 200,
+        max_depth: 10,
+        ..options
+    };
+    let mut camera = Camera::new(&Arc::new(scene), 1, 1, options);
+
+    measure("render: demo scene, 200px, 2 passes", || {
+        // Resizing restarts the render from no samples.
+        camera.resize(200);
+        camera.render_single_threaded(2);
+    });
+}
+
+fn hit() {
+    // `Scene::hit` alone, for comparing builds with and without `--features simd`.
+    let (scene, _) = random_spheres(&mut Rng::new(0));
+    let mut rng = Rng::new(1);
+    let rays: Vec<Ray> = (0..100_000)
+        .map(|_| {
+            let pos = Vec3::new(
+                rng.random_f64_range(-12.0, 12.0),
+                rng.random_f64_range(0.1, 3.0),
+                rng.random_f64_range(-12.0, 12.0),
+            );
+            Ray::new(pos, Vec3::random_unit_vector(&mut rng))
+        })
+        .collect();
+
+    measure("hit: demo scene, 100k random rays", || {
+        rays.iter()
+            .filter(|r| scene.hit(r, 0.001, f64::INFINITY).is_some())
+            .count()
+    });
+}
+
+fn sphere() {
+    // `Sphere::hit` on its own, with rays from all around aimed near its center so most of
+    // them hit and reach the normal calculation.
+    let mat = Arc::new(Material::lambertian(Color::new(0.5, 0.5, 0.5)));
+    let sphere = Sphere::new(Vec3::new(0.0, 0.0, 0.0), 1.0, mat);
+    let mut rng = Rng::new(1);
+    let rays: Vec<Ray> = (0..1_000_000)
+        .map(|_| {
+            let pos = 3.0 * Vec3::random_unit_vector(&mut rng);
+            let target = Vec3::random_range(&mut rng, -1.0, 1.0);
+            Ray::new(pos, target - pos)
+        })
+        .collect();
+
+    measure("sphere: 1M rays at one sphere", || {
+        rays.iter()
+            .filter_map(|r| sphere.hit(r, 0.001, f64::INFINITY))
+            .map(|rec| rec.t)
+            .sum::<f64>()
+    });
+}
+
+fn passes() {
+    // Render passes on eight view threads, taking one or four samples per pixel in each pass,
+    // for the same total of samples.
+    let (scene, options) = random_spheres(&mut Rng::new(0));
+    let options = CameraOptions {
+        image_width: 100,
+        max_depth: 10,
+        ..options
+    };
+    let mut camera = Camera::new(&Arc::new(scene), 1, 8, options);
+
+    for (samples_per_pass, passes) in [(1, 8), (4, 2)] {
+        camera.set_samples_per_pass(samples_per_pass);
+        let label = format!("passes: {samples_per_pass} samples per pass, {passes} passes");
+        let median = measure(&label, || {
+            camera.resize(100);
+            camera.render_to_completion(passes);
+        });
+        let passes_per_sec = passes as f64 / median.as_secs_f64();
+        println!(
+            "{:<40} {passes_per_sec:.1} passes/s, {:.1} samples per pixel/s",
+            "",
+            passes_per_sec * samples_per_pass as f64
+        );
+    }
+}
+
+fn four_k() {
+    // One pass of a 4K image of an empty scene, so the time is mostly taken by adding samples to
+    // their sums, for comparing builds with and without `--features f32-accum`.
+    let options = CameraOptions {
+        aspect_ratio: 16.0 / 9.0,
+        image_width: 3840,
+        ..Default::default()
+    };
+    let mut camera = Camera::new(&Arc::new(Scene::new()), 1, 1, options);
+
+    let median = measure("4k: empty scene, 3840x2160, 1 pass", || {
+        camera.resize(3840);
+        camera.render_single_threaded(1);
+    });
+    let pixels = camera.get_width() * camera.get_height();
+    println!(
+        "{:<40} {:.1} Mpixels/s, {} MiB of sums",
+        "",
+        pixels as f64 / median.as_secs_f64() / 1.0e6,
+        camera.sample_buffer_bytes() >> 20
+    );
+}
+
+fn order() {
+    // The render loop on the demo scene in each pixel order, on one thread like `render`.
+    for pixel_order in [PixelOrder::Scanline, PixelOrder::Morton] {
+        let (scene, options) = random_spheres(&mut Rng::new(0));
+        let options = CameraOptions {
+            image_width: 200,
+            max_depth: 10,
+            pixel_order,
+            ..options
+        };
+        let mut camera = Camera::new(&Arc::new(scene), 1, 1, options);
+
+        measure(&format!("order: {pixel_order:?}, 200px, 2 passes"), || {
+            camera.resize(200);
+            camera.render_single_threaded(2);
+        });
+    }
+}
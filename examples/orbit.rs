@@ -0,0 +1,52 @@
+use raytracing::animate::*;
+use raytracing::camera::*;
+use raytracing::color::*;
+use raytracing::material::*;
+use raytracing::scene::*;
+use raytracing::sphere::*;
+use raytracing::vec3::*;
+
+use std::sync::Arc;
+
+fn main() -> std::io::Result<()> {
+    let ground = Arc::new(Material::lambertian(Color::new(0.5, 0.5, 0.5)));
+    let center = Arc::new(Material::lambertian(Color::new(0.4, 0.2, 0.1)));
+    let orbiter = Arc::new(Material::metal(Color::new(0.7, 0.6, 0.5), 0.0));
+
+    animate_with_scenes(
+        AnimationOptions {
+            quiet: std::env::args().any(|arg| arg == "--quiet"),
+            ..Default::default()
+        },
+        |_, t| {
+            let angle = 2.0 * std::f64::consts::PI * t;
+
+            let mut scene = Scene::new();
+            scene.add(Sphere::new(
+                Vec3::new(0.0, -1000.0, 0.0),
+                1000.0,
+                Arc::clone(&ground),
+            ));
+            scene.add(Sphere::new(
+                Vec3::new(0.0, 1.0, 0.0),
+                1.0,
+                Arc::clone(&center),
+            ));
+            scene.add(Sphere::new(
+                Vec3::new(2.5 * angle.cos(), 0.5, 2.5 * angle.sin()),
+                0.5,
+                Arc::clone(&orbiter),
+            ));
+            Arc::new(scene)
+        },
+        |_, _| CameraOptions {
+            aspect_ratio: 16.0 / 9.0,
+            image_width: 400,
+            max_depth: 50,
+            vfov: 30.0,
+            lookfrom: Vec3::new(0.0, 4.0, 10.0),
+            lookat: Vec3::new(0.0, 0.5, 0.0),
+            ..Default::default()
+        },
+    )
+}
@@ -0,0 +1,78 @@
+use crate::ray::*;
+use crate::vec3::*;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    min: Vec3,
+    max: Vec3,
+}
+
+impl Aabb {
+    pub fn empty() -> Self {
+        // Inside out, so it contains nothing and is the identity for `union`.
+        Self {
+            min: Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    pub fn new(a: Vec3, b: Vec3) -> Self {
+        // Treat the two points as extremes of the box; they don't need to be ordered.
+        Self {
+            min: Vec3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z())),
+            max: Vec3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z())),
+        }
+    }
+
+    pub fn disc(center: Vec3, axis: Vec3, radius: f64) -> Self {
+        // A disc facing along the unit vector `axis` extends less along axes closer to it.
+        let extent = |a: f64| radius * (1.0 - a * a).max(0.0).sqrt();
+        let e = Vec3::new(extent(axis.x()), extent(axis.y()), extent(axis.z()));
+        Self::new(center - e, center + e)
+    }
+
+    pub fn min(&self) -> Vec3 {
+        self.min
+    }
+
+    pub fn max(&self) -> Vec3 {
+        self.max
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            min: Vec3::new(
+                self.min.x().min(other.min.x()),
+                self.min.y().min(other.min.y()),
+                self.min.z().min(other.min.z()),
+            ),
+            max: Vec3::new(
+                self.max.x().max(other.max.x()),
+                self.max.y().max(other.max.y()),
+                self.max.z().max(other.max.z()),
+            ),
+        }
+    }
+
+    pub fn hit(&self, r: &Ray, mut ray_tmin: f64, mut ray_tmax: f64) -> bool {
+        // Slab test: clip the ray's `t` range by the pair of planes bounding each axis.
+        let axes = self.min.iter().zip(self.max.iter());
+        for ((min, max), (pos, dir)) in axes.zip(r.pos.iter().zip(r.dir.iter())) {
+            let inv_dir = 1.0 / dir;
+            let mut t0 = (min - pos) * inv_dir;
+            let mut t1 = (max - pos) * inv_dir;
+            if inv_dir < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            ray_tmin = ray_tmin.max(t0);
+            ray_tmax = ray_tmax.min(t1);
+            // Flat boxes, e.g. around axis-aligned triangles, still count as hit.
+            if ray_tmax < ray_tmin {
+                return false;
+            }
+        }
+
+        true
+    }
+}
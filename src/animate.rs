@@ -0,0 +1,79 @@
+use crate::camera::*;
+use crate::scene::*;
+
+use std::sync::Arc;
+
+pub struct AnimationOptions {
+    pub frames: usize, // Number of frames to render
+    pub passes: usize, // Render passes (samples per pixel) for each frame
+    pub num_views: u8, // Number of view threads for each frame
+    pub rng_seed: u64, // Random seed shared by every frame
+    pub quiet: bool,   // Don't show progress on stderr
+}
+
+impl Default for AnimationOptions {
+    fn default() -> Self {
+        Self {
+            frames: 24,
+            passes: 100,
+            num_views: 4,
+            rng_seed: 0,
+            quiet: false,
+        }
+    }
+}
+
+// Renders frames of `scene` to `frame_0000.png`, `frame_0001.png`, etc. in the current directory.
+// `camera_at` is given the frame number and its time `t` in [0,1).
+pub fn animate<C>(
+    options: AnimationOptions,
+    scene: &Arc<Scene>,
+    camera_at: C,
+) -> std::io::Result<()>
+where
+    C: FnMut(usize, f64) -> CameraOptions,
+{
+    animate_with_scenes(options, |_, _| Arc::clone(scene), camera_at)
+}
+
+// Like `animate`, but with a scene for each frame from `scene_at`, given the same as `camera_at`.
+pub fn animate_with_scenes<S, C>(
+    options: AnimationOptions,
+    mut scene_at: S,
+    mut camera_at: C,
+) -> std::io::Result<()>
+where
+    S: FnMut(usize, f64) -> Arc<Scene>,
+    C: FnMut(usize, f64) -> CameraOptions,
+{
+    // One camera renders every frame, so its view threads are reused; changing its options
+    // or scene restarts accumulation for the next frame.
+    let mut camera: Option<Camera> = None;
+    for frame in 0..options.frames {
+        let t = frame as f64 / options.frames as f64;
+        let scene = scene_at(frame, t);
+        let camera_options = camera_at(frame, t);
+        let camera = match camera.as_mut() {
+            None => camera.insert(Camera::new(
+                &scene,
+                options.rng_seed,
+                options.num_views,
+                camera_options,
+            )),
+            Some(camera) => {
+                camera.set_scene(&scene);
+                camera.set_options(camera_options);
+                camera
+            }
+        };
+
+        let label = format!("frame {}/{}: ", frame + 1, options.frames);
+        camera.render_to_completion_with_progress(
+            options.passes,
+            (!options.quiet).then_some(label.as_str()),
+        );
+        camera.save_png(format!("frame_{frame:04}.png"))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use crate::color::*;
+use crate::sky::*;
+use crate::vec3::*;
+
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub enum Background {
+    // Blends from white straight down, through half white at the horizon, to light blue at `up`.
+    Gradient { up: Vec3 },
+    // The same color in every direction; black for no environment light.
+    Solid(Color),
+    // Whatever the function gives for each direction, which needn't be a unit vector.
+    Procedural(Arc<dyn Fn(Vec3) -> Color + Send + Sync>),
+    // A daylight sky and sun.
+    Sky(Sky),
+}
+
+impl std::fmt::Debug for Background {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Gradient { up } => f.debug_struct("Gradient").field("up", up).finish(),
+            Self::Solid(color) => f.debug_tuple("Solid").field(color).finish(),
+            Self::Procedural(_) => f.write_str("Procedural(..)"),
+            Self::Sky(sky) => f.debug_tuple("Sky").field(sky).finish(),
+        }
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self::Gradient {
+            up: Vec3::new(0.0, 1.0, 0.0),
+        }
+    }
+}
+
+impl Background {
+    pub fn procedural<F: Fn(Vec3) -> Color + Send + Sync + 'static>(f: F) -> Self {
+        // E.g. a red ground below a blue sky:
+        //     Background::procedural(|dir| {
+        //         if dir.y() < 0.0 {
+        //             Color::new(0.8, 0.1, 0.1)
+        //         } else {
+        //             Color::new(0.2, 0.4, 0.9)
+        //         }
+        //     })
+        Self::Procedural(Arc::new(f))
+    }
+
+    pub fn color(&self, dir: Vec3) -> Color {
+        match self {
+            Self::Gradient { up } => {
+                let a = 0.5 * (dir.unit().dot(up.unit()) + 1.0);
+                (1.0 - a) * Color::new(1.0, 1.0, 1.0) + a * Color::new(0.5, 0.7, 1.0)
+            }
+            Self::Solid(color) => *color,
+            Self::Procedural(f) => f(dir),
+            Self::Sky(sky) => sky.color(dir),
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::io::{self, Write};
+
+// Writes an 8-bit RGBA image as an uncompressed 24-bit BMP, dropping the alpha channel.
+pub fn write_rgba8<W: Write>(mut w: W, width: u32, height: u32, rgba: &[u8]) -> io::Result<()> {
+    assert_eq!(rgba.len(), 4 * width as usize * height as usize);
+
+    // Each row of BGR pixels is padded to a multiple of four bytes.
+    let row_len = (3 * width as usize).next_multiple_of(4);
+    let image_size = (row_len * height as usize) as u32;
+    let header_size = 14 + 40;
+
+    // BITMAPFILEHEADER
+    w.write_all(b"BM")?;
+    w.write_all(&(header_size + image_size).to_le_bytes())?;
+    w.write_all(&[0, 0, 0, 0])?; // reserved
+    w.write_all(&header_size.to_le_bytes())?; // offset of pixel data
+
+    // BITMAPINFOHEADER
+    w.write_all(&40_u32.to_le_bytes())?; // header size
+    w.write_all(&(width as i32).to_le_bytes())?;
+    w.write_all(&(height as i32).to_le_bytes())?; // positive, so rows go bottom to top
+    w.write_all(&1_u16.to_le_bytes())?; // color planes
+    w.write_all(&24_u16.to_le_bytes())?; // bits per pixel
+    w.write_all(&0_u32.to_le_bytes())?; // no compression
+    w.write_all(&image_size.to_le_bytes())?;
+    w.write_all(&2835_i32.to_le_bytes())?; // horizontal resolution, 72 DPI in pixels per metre
+    w.write_all(&2835_i32.to_le_bytes())?; // vertical resolution
+    w.write_all(&0_u32.to_le_bytes())?; // palette size
+    w.write_all(&0_u32.to_le_bytes())?; // important colors
+
+    let mut row = vec![0_u8; row_len];
+    if width > 0 {
+        for rgba_row in rgba.chunks_exact(4 * width as usize).rev() {
+            for (bgr, p) in row.chunks_exact_mut(3).zip(rgba_row.chunks_exact(4)) {
+                bgr.copy_from_slice(&[p[2], p[1], p[0]]);
+            }
+            w.write_all(&row)?;
+        }
+    }
+
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_describes_padded_rows() {
+        // Three pixels are nine bytes of BGR, padded to twelve, on each of two rows.
+        let rgba: Vec<u8> = (0..24).collect();
+        let mut bmp = vec![];
+        write_rgba8(&mut bmp, 3, 2, &rgba).expect("write_rgba8");
+
+        let u32_at = |i: usize| u32::from_le_bytes(bmp[i..i + 4].try_into().expect("u32"));
+        let u16_at = |i: usize| u16::from_le_bytes(bmp[i..i + 2].try_into().expect("u16"));
+        assert_eq!(bmp.len(), 54 + 2 * 12);
+        assert_eq!(&bmp[0..2], b"BM");
+        assert_eq!(u32_at(2), bmp.len() as u32); // file size
+        assert_eq!(u32_at(10), 54); // offset of pixel data
+        assert_eq!(u32_at(18), 3); // width
+        assert_eq!(u32_at(22), 2); // height
+        assert_eq!(u16_at(28), 24); // bits per pixel
+        assert_eq!(u32_at(34), 2 * 12); // image size
+
+        // The bottom row comes first, then padding.
+        assert_eq!(&bmp[54..66], &[14, 13, 12, 18, 17, 16, 22, 21, 20, 0, 0, 0]);
+        assert_eq!(&bmp[66..78], &[2, 1, 0, 6, 5, 4, 10, 9, 8, 0, 0, 0]);
+    }
+}
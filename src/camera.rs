@@ -1,24 +1,101 @@
+use crate::aabb::*;
+use crate::background::*;
+use crate::bmp;
 use crate::color::*;
+use crate::hit_record::*;
+use crate::light::*;
+use crate::material::*;
+use crate::png;
+use crate::pool::*;
+use crate::post::*;
 use crate::random::*;
 use crate::ray::*;
 use crate::scene::*;
 use crate::vec3::*;
 
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{Receiver, SyncSender};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender, TryRecvError};
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+#[derive(Copy, Clone, Debug)]
+pub enum RenderMode {
+    Shaded,
+    // Grayscale distance to first hit, black at `near` to white at `far`, which must be further
+    // away; misses are white.
+    Depth { near: f64, far: f64 },
+    // Material albedos lit only by the scene's lights, with hard shadows and no bounces.
+    DirectLighting,
+    // Gray clay shaded by the fraction of `samples` rays from the first hit that travel
+    // `radius` without hitting anything; misses are white.
+    AmbientOcclusion { radius: f64, samples: u16 },
+}
+
+impl RenderMode {
+    fn is_valid(&self) -> bool {
+        // Depths are scaled by `far - near`, which has to be positive.
+        match *self {
+            Self::Depth { near, far } => far > near,
+            Self::Shaded | Self::DirectLighting | Self::AmbientOcclusion { .. } => true,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RenderStatus {
+    InProgress,
+    Completed, // Every pass up to `max_passes` is done
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct StereoOptions {
+    pub eye_separation: f64, // Distance between the left and right eye positions
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum PixelFilter {
+    Box,  // Equal weights for samples within the pixel
+    Tent, // Weights falling off linearly to zero one pixel away from the pixel center
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum PixelOrder {
+    Scanline, // Row by row, left to right
+    Morton,   // Bands of `MORTON_BAND_ROWS` rows, each in Z-order of 4-pixel chunks
+}
+
+// Rows in each band of pixels rendered in Morton order; pausing waits for the band to finish.
+const MORTON_BAND_ROWS: usize = 8;
 
 pub struct CameraOptions {
-    pub aspect_ratio: f64,  // Ratio of image width over height
-    pub image_width: u16,   // Rendered image width in pixel count
-    pub max_depth: u16,     // Maximum number of ray bounces into scene
-    pub vfov: f64,          // Vertical view angle (field of view)
-    pub lookfrom: Vec3,     // Point camera is looking from
-    pub lookat: Vec3,       // Point camera is looking at
-    pub vup: Vec3,          // Camera-relative "up" direction
-    pub defocus_angle: f64, // Variation angle of rays through each pixel.
-    pub focus_dist: f64,    // Distance from camera lookfrom point to plane of perfect focus.
+    pub aspect_ratio: f64,         // Ratio of image width over height
+    pub image_width: u16,          // Rendered image width in pixel count
+    pub max_depth: u16,            // Maximum number of ray bounces into scene
+    pub ray_epsilon: f64,          // Minimum `t` of ray hits, to avoid self-intersection
+    pub max_tests: Option<usize>,  // Objects each ray may test before taking its best hit yet
+    pub samples_per_pass: u16,     // Samples taken for each pixel in each render pass
+    pub pixel_filter: PixelFilter, // Reconstruction filter used to weight samples
+    pub pixel_order: PixelOrder,   // Order pixels are rendered in; the image is the same either way
+    pub gamma: f64,                // Gamma of output pixels; 2.0 takes the square root
+    pub vfov: f64,                 // Vertical view angle (field of view)
+    pub lookfrom: Vec3,            // Point camera is looking from
+    pub lookat: Vec3,              // Point camera is looking at
+    pub vup: Vec3,                 // Camera-relative "up" direction
+    pub defocus_angle: f64,        // Variation angle of rays through each pixel.
+    pub focus_dist: f64,           // Distance from camera lookfrom point to plane of perfect focus.
+    pub aperture_blades: u8,       // Sides of polygonal aperture; circular if less than three
+    pub background: Background,    // Color of rays that miss the scene
+    pub sky_intensity: f64,        // Multiplier for the background color
+    pub alpha_background: bool,    // Leave pixels transparent where camera rays miss the scene
+    pub render_mode: RenderMode,   // How each camera ray is turned into a color
+    pub bbox_overlay: bool,        // Draw each object's bounding box over the image
+    pub max_passes: Option<usize>, // Stop rendering after this many passes
+    pub override_material: Option<Arc<Material>>, // Scatter off this instead of hit materials
+    pub stereo: Option<StereoOptions>, // Render left and right eye images side by side
+    pub post_effects: Vec<PostEffect>, // Applied in order to frames taken with `frame_rgba8`
 }
 
 impl Default for CameraOptions {
@@ -27,149 +104,465 @@ impl Default for CameraOptions {
             aspect_ratio: 1.0,
             image_width: 100,
             max_depth: 10,
+            ray_epsilon: 0.001,
+            max_tests: None,
+            samples_per_pass: 1,
+            pixel_filter: PixelFilter::Box,
+            pixel_order: PixelOrder::Scanline,
+            gamma: 2.0,
             vfov: 90.0,
             lookfrom: Vec3::new(0.0, 0.0, 0.0),
             lookat: Vec3::new(0.0, 0.0, -1.0),
             vup: Vec3::new(0.0, 1.0, 0.0),
             defocus_angle: 0.0,
             focus_dist: 10.0,
+            aperture_blades: 0,
+            background: Background::default(),
+            sky_intensity: 1.0,
+            alpha_background: false,
+            render_mode: RenderMode::Shaded,
+            bbox_overlay: false,
+            max_passes: None,
+            override_material: None,
+            stereo: None,
+            post_effects: vec![],
+        }
+    }
+}
+
+impl CameraOptions {
+    pub fn with_thin_lens(self, focal_length_mm: f64, f_number: f64, sensor_width_mm: f64) -> Self {
+        // Sets `vfov` and `defocus_angle` from a lens focused at `focus_dist`, taking scene
+        // units to be metres. The image covers the sensor's width, so the sensor height is
+        // `sensor_width_mm / aspect_ratio`, and the vertical field of view is the angle that
+        // height subtends from the lens:
+        //     vfov = 2 * atan(sensor_height_mm / (2 * focal_length_mm))
+        // The aperture diameter is `focal_length_mm / f_number`, and `defocus_angle` is the angle
+        // it subtends from the plane of focus:
+        //     defocus_angle = 2 * atan(aperture_diameter / (2 * focus_dist))
+        let sensor_height_mm = sensor_width_mm / self.aspect_ratio;
+        let vfov = 2.0 * (sensor_height_mm / (2.0 * focal_length_mm)).atan();
+
+        let aperture_diameter = focal_length_mm / f_number / 1000.0;
+        let defocus_angle = 2.0 * (aperture_diameter / (2.0 * self.focus_dist)).atan();
+
+        Self {
+            vfov: vfov.to_degrees(),
+            defocus_angle: defocus_angle.to_degrees(),
+            ..self
         }
     }
 }
 
 pub struct Camera {
+    options: CameraOptions,
+    scene: Arc<Scene>,
+    rng_seed: u64,
     image_width: usize,
     image_height: usize,
+    views: Vec<Arc<Mutex<View>>>,
     pixel_bufs: Vec<Arc<Mutex<Vec<u8>>>>,
+    pixels_rendered: Vec<Arc<AtomicUsize>>,
     view_xs: Vec<usize>,
     view_widths: Vec<usize>,
     passes_wanted: usize,
+    paused: bool,
     pause: Arc<AtomicBool>,
-    passes_wanted_txs: Vec<SyncSender<usize>>,
+    view_workers: Vec<ViewWorker>,
     passes_done_rxs: Vec<Receiver<usize>>,
+    steps_in_flight: Vec<bool>, // Views rendering a pass requested by `try_render_step`
+    step_passes_done: Vec<usize>, // Passes each view last said it had done in `try_render_step`
+    view_threads: Vec<JoinHandle<()>>,
+    // Copies of the first view's camera center and pixel locations, for `pixel_ray`.
+    center: Vec3,
+    pixel00_loc: Vec3,
+    pixel_delta_u: Vec3,
+    pixel_delta_v: Vec3,
 }
 
-impl Camera {
-    pub fn new(scene: &Arc<Scene>, rng_seed: u64, num_views: u8, options: CameraOptions) -> Self {
-        let i_width_usize = options.image_width as usize;
-        let i_height_usize = usize::max(
-            1,
-            (options.image_width as f64 / options.aspect_ratio) as usize,
-        );
-
-        let image_width = i_width_usize as f64;
-        let image_height = i_height_usize as f64;
-
-        let center = options.lookfrom;
-
-        // Determine viewport dimensions.
-        let theta = options.vfov.to_radians();
-        let h = (theta / 2.0).tan();
-        let viewport_height = 2.0 * h * options.focus_dist;
-        let viewport_width = viewport_height * image_width / image_height;
-
-        // Calculate the u,v,w unit basis vectors for the camera coordinate frame.
-        let w = (options.lookfrom - options.lookat).unit();
-        let u = options.vup.cross(w).unit();
-        let v = w.cross(u);
-
-        // Calculate the vectors across the horizontal and down the vertical viewport edges.
-        let viewport_u = viewport_width * u; // Vector across viewport horizontal edge
-        let viewport_v = viewport_height * -v; // Vector across viewport vertical edge
-
-        // Calculate the horizontal and vertical delta vectors from pixel to pixel.
-        let pixel_delta_u = viewport_u / image_width;
-        let pixel_delta_v = viewport_v / image_height;
+// What renders a view when the camera asks for passes, given how many are wanted.
+enum ViewWorker {
+    Thread(SyncSender<usize>), // A thread of the view's own
+    Pool(Arc<RenderPool>, Arc<dyn Fn(usize) + Send + Sync>), // Jobs sent to shared threads
+}
 
-        // Calculate the location of the upper left pixel.
-        let viewport_upper_left =
-            center - options.focus_dist * w - viewport_u / 2.0 - viewport_v / 2.0;
-        let pixel00_loc = viewport_upper_left + 0.5 * (pixel_delta_u + pixel_delta_v);
+impl ViewWorker {
+    fn request(&self, passes_wanted: usize) {
+        match self {
+            Self::Thread(passes_wanted_tx) => {
+                passes_wanted_tx
+                    .send(passes_wanted)
+                    .expect("passes_wanted_tx");
+            }
+            Self::Pool(pool, render_view) => {
+                let render_view = Arc::clone(render_view);
+                pool.spawn(move || render_view(passes_wanted));
+            }
+        }
+    }
+}
 
-        // Calculate the defocus disk basis vectors.
-        let defocus_radius = options.focus_dist * (options.defocus_angle / 2.0).to_radians().tan();
-        let defocus_disk_u = defocus_radius * u;
-        let defocus_disk_v = defocus_radius * v;
+impl Camera {
+    pub fn new(scene: &Arc<Scene>, rng_seed: u64, num_views: u8, options: CameraOptions) -> Self {
+        Self::create(scene, rng_seed, num_views, options, None)
+    }
 
-        // Create multiple vertical views to cover the camera's full view of the scene.
+    pub fn with_pool(
+        scene: &Arc<Scene>,
+        rng_seed: u64,
+        num_views: u8,
+        options: CameraOptions,
+        pool: &Arc<RenderPool>,
+    ) -> Self {
+        // Like `new`, but rendered by the pool's threads instead of a thread for each view, so
+        // several cameras can share them. Views wait for a free thread in the order they were
+        // requested, so `render` may only reach the later ones if there are too few threads.
+        Self::create(scene, rng_seed, num_views, options, Some(pool))
+    }
 
+    fn create(
+        scene: &Arc<Scene>,
+        rng_seed: u64,
+        num_views: u8,
+        options: CameraOptions,
+        pool: Option<&Arc<RenderPool>>,
+    ) -> Self {
         let num_views = num_views as usize;
 
         assert!(num_views > 0);
-        assert!(num_views <= i_width_usize);
+        assert!(num_views <= options.image_width as usize);
+        assert!(options.stereo.is_none() || num_views > 1);
+        assert!(options.render_mode.is_valid());
+
+        let pause = Arc::new(AtomicBool::new(false));
+        let (image_width, image_height, new_views) =
+            Self::create_views(&options, scene, rng_seed, num_views, &pause);
+
+        // The first view starts at the left edge, so its pixel locations are the whole image's.
+        let center = new_views[0].center;
+        let pixel00_loc = new_views[0].pixel00_loc;
+        let pixel_delta_u = new_views[0].pixel_delta_u;
+        let pixel_delta_v = new_views[0].pixel_delta_v;
 
+        let mut views: Vec<Arc<Mutex<View>>> = vec![];
         let mut pixel_bufs: Vec<Arc<Mutex<Vec<u8>>>> = vec![];
+        let mut pixels_rendered: Vec<Arc<AtomicUsize>> = vec![];
         let mut view_xs: Vec<usize> = vec![];
         let mut view_widths: Vec<usize> = vec![];
-        let pause = Arc::new(AtomicBool::new(false));
-        let mut passes_wanted_txs: Vec<SyncSender<usize>> = vec![];
+        let mut view_workers: Vec<ViewWorker> = vec![];
         let mut passes_done_rxs: Vec<Receiver<usize>> = vec![];
+        let mut view_threads: Vec<JoinHandle<()>> = vec![];
 
-        for i in 0..num_views {
-            let view_x = i * i_width_usize / num_views;
-            let view_width = (i + 1) * i_width_usize / num_views - view_x;
-
-            let pixel_buf = Arc::new(Mutex::new(vec![0_u8; 4 * view_width * i_height_usize]));
+        for view in new_views {
+            let pixel_buf = Arc::new(Mutex::new(vec![0_u8; 4 * view.width * view.height]));
 
             pixel_bufs.push(Arc::clone(&pixel_buf));
-            view_xs.push(view_x);
-            view_widths.push(view_width);
+            pixels_rendered.push(Arc::clone(&view.pixels_rendered));
+            view_xs.push(view.eye_x + view.view_x);
+            view_widths.push(view.width);
 
-            let (passes_wanted_tx, passes_wanted_rx) = std::sync::mpsc::sync_channel::<usize>(0);
-            let (passes_done_tx, passes_done_rx) = std::sync::mpsc::sync_channel::<usize>(0);
+            // The view is shared so the camera can reconfigure it between render requests.
+            let view = Arc::new(Mutex::new(view));
+            views.push(Arc::clone(&view));
 
-            passes_wanted_txs.push(passes_wanted_tx);
+            let (passes_done_tx, passes_done_rx) = std::sync::mpsc::sync_channel::<usize>(0);
             passes_done_rxs.push(passes_done_rx);
 
-            std::thread::spawn({
-                let scene = Arc::clone(scene);
-                let rng_seed = rng_seed + i as u64;
-                let pause = pause.clone();
-
-                move || {
-                    let mut view = View {
-                        color_buf: vec![Color::new(0.0, 0.0, 0.0); view_width * i_height_usize],
-                        width: view_width,
-                        height: i_height_usize,
-                        max_depth: options.max_depth,
-                        start_row: 0,
-                        render_passes: 0,
-                        pause,
-                        pixel00_loc: pixel00_loc + view_x as f64 * pixel_delta_u,
-                        pixel_delta_u,
-                        pixel_delta_v,
-                        center,
-                        defocus_angle: options.defocus_angle,
-                        defocus_disk_u,
-                        defocus_disk_v,
-                    };
-                    let mut rng = Rng::new(rng_seed);
-
-                    while let Ok(passes_wanted) = passes_wanted_rx.recv() {
-                        let mut pixel_buf = pixel_buf.lock().expect("pixel_buf mutex");
-                        view.render(&mut rng, &scene, &mut pixel_buf[..], passes_wanted);
-                        drop(pixel_buf);
-                        passes_done_tx
-                            .send(view.render_passes)
-                            .expect("passes_done_tx");
-                    }
+            let render_view = move |passes_wanted: usize| {
+                // The scene is taken from the view rather than captured, so it can be replaced.
+                let mut view = view.lock().expect("view mutex");
+                let scene = Arc::clone(&view.scene);
+                view.render(&scene, passes_wanted);
+
+                // Render into the view's own buffer, then copy it to the shared one, so the
+                // shared buffer is only locked briefly and always has whole rows.
+                view.copy_pixels(&scene, &mut pixel_buf.lock().expect("pixel_buf mutex"));
+
+                let render_passes = view.render_passes;
+                drop(view);
+                passes_done_tx.send(render_passes).expect("passes_done_tx");
+            };
+
+            view_workers.push(match pool {
+                None => {
+                    let (passes_wanted_tx, passes_wanted_rx) =
+                        std::sync::mpsc::sync_channel::<usize>(0);
+                    view_threads.push(std::thread::spawn(move || {
+                        while let Ok(passes_wanted) = passes_wanted_rx.recv() {
+                            render_view(passes_wanted);
+                        }
+                    }));
+                    ViewWorker::Thread(passes_wanted_tx)
                 }
+                Some(pool) => ViewWorker::Pool(Arc::clone(pool), Arc::new(render_view)),
             });
         }
 
         Self {
-            image_width: i_width_usize,
-            image_height: i_height_usize,
+            options,
+            scene: Arc::clone(scene),
+            rng_seed,
+            image_width,
+            image_height,
+            views,
             pixel_bufs,
+            pixels_rendered,
             view_xs,
             view_widths,
             passes_wanted: 0,
+            paused: false,
             pause,
-            passes_wanted_txs,
+            view_workers,
             passes_done_rxs,
+            steps_in_flight: vec![false; num_views],
+            step_passes_done: vec![0; num_views],
+            view_threads,
+            center,
+            pixel00_loc,
+            pixel_delta_u,
+            pixel_delta_v,
         }
     }
 
+    fn create_views(
+        options: &CameraOptions,
+        scene: &Arc<Scene>,
+        rng_seed: u64,
+        num_views: usize,
+        pause: &Arc<AtomicBool>,
+    ) -> (usize, usize, Vec<View>) {
+        let i_width_usize = options.image_width as usize;
+        let i_height_usize = usize::max(
+            1,
+            (options.image_width as f64 / options.aspect_ratio) as usize,
+        );
+
+        let image_width = i_width_usize as f64;
+        let image_height = i_height_usize as f64;
+
+        // Stereo renders put a left and right eye image side by side, each with half the views.
+        // Both eyes converge on `lookat`, and the left eye's pixels are seeded as in mono renders.
+        let eyes = match options.stereo {
+            None => vec![(options.lookfrom, num_views)],
+            Some(stereo) => {
+                let u = options.vup.cross(options.lookfrom - options.lookat).unit();
+                let offset = stereo.eye_separation / 2.0 * u;
+                vec![
+                    (options.lookfrom - offset, num_views.div_ceil(2)),
+                    (options.lookfrom + offset, num_views / 2),
+                ]
+            }
+        };
+
+        let mut views: Vec<View> = vec![];
+
+        for (eye, (center, eye_views)) in eyes.iter().copied().enumerate() {
+            // Determine viewport dimensions.
+            let theta = options.vfov.to_radians();
+            let h = (theta / 2.0).tan();
+            let viewport_height = 2.0 * h * options.focus_dist;
+            let viewport_width = viewport_height * image_width / image_height;
+
+            // Calculate the u,v,w unit basis vectors for the camera coordinate frame.
+            let w = (center - options.lookat).unit();
+            let u = options.vup.cross(w).unit();
+            let v = w.cross(u);
+
+            // Calculate the vectors across the horizontal and down the vertical viewport edges.
+            let viewport_u = viewport_width * u; // Vector across viewport horizontal edge
+            let viewport_v = viewport_height * -v; // Vector across viewport vertical edge
+
+            // Calculate the horizontal and vertical delta vectors from pixel to pixel.
+            let pixel_delta_u = viewport_u / image_width;
+            let pixel_delta_v = viewport_v / image_height;
+
+            // Calculate the location of the upper left pixel.
+            let viewport_upper_left =
+                center - options.focus_dist * w - viewport_u / 2.0 - viewport_v / 2.0;
+            let pixel00_loc = viewport_upper_left + 0.5 * (pixel_delta_u + pixel_delta_v);
+
+            // Calculate the defocus disk basis vectors.
+            let defocus_radius =
+                options.focus_dist * (options.defocus_angle / 2.0).to_radians().tan();
+            let defocus_disk_u = defocus_radius * u;
+            let defocus_disk_v = defocus_radius * v;
+
+            // Create multiple vertical views to cover the eye's full view of the scene.
+
+            views.extend((0..eye_views).map(|i| {
+                let view_x = i * i_width_usize / eye_views;
+                let view_width = (i + 1) * i_width_usize / eye_views - view_x;
+
+                View {
+                    scene: Arc::clone(scene),
+                    color_buf: vec![[0.0; 3]; view_width * i_height_usize],
+                    alpha_buf: vec![0.0; view_width * i_height_usize],
+                    weight_buf: vec![0.0; view_width * i_height_usize],
+                    square_buf: vec![0.0; view_width * i_height_usize],
+                    pixel_buf: vec![0_u8; 4 * view_width * i_height_usize],
+                    width: view_width,
+                    height: i_height_usize,
+                    region: (0, 0, view_width, i_height_usize),
+                    max_depth: options.max_depth,
+                    ray_epsilon: options.ray_epsilon,
+                    max_tests: options.max_tests,
+                    samples_per_pass: options.samples_per_pass.max(1),
+                    pixel_filter: options.pixel_filter,
+                    pixel_order: options.pixel_order,
+                    gamma: options.gamma,
+                    render_mode: options.render_mode,
+                    bbox_overlay: options.bbox_overlay,
+                    start_row: 0,
+                    render_passes: 0,
+                    pixels_rendered: Arc::new(AtomicUsize::new(0)),
+                    rng_seed: rng_seed.wrapping_add((eye as u64) << 63),
+                    eye_x: eye * i_width_usize,
+                    view_x,
+                    image_width: i_width_usize,
+                    pause: Arc::clone(pause),
+                    pixel00_loc: pixel00_loc + view_x as f64 * pixel_delta_u,
+                    pixel_delta_u,
+                    pixel_delta_v,
+                    center,
+                    defocus_angle: options.defocus_angle,
+                    aperture_blades: options.aperture_blades,
+                    background: options.background.clone(),
+                    sky_intensity: options.sky_intensity,
+                    alpha_background: options.alpha_background,
+                    override_material: options.override_material.clone(),
+                    defocus_disk_u,
+                    defocus_disk_v,
+                }
+            }));
+        }
+
+        (eyes.len() * i_width_usize, i_height_usize, views)
+    }
+
+    pub fn resize(&mut self, new_width: u16) {
+        // Rebuild every view at the new size and restart accumulation, reusing the view threads.
+        // Each view needs to be at least one pixel wide.
+        self.finish_steps();
+        let num_views = self.views.len();
+        self.options.image_width = new_width.max(num_views as u16);
+
+        let (image_width, image_height, new_views) = Self::create_views(
+            &self.options,
+            &self.scene,
+            self.rng_seed,
+            num_views,
+            &self.pause,
+        );
+
+        self.center = new_views[0].center;
+        self.pixel00_loc = new_views[0].pixel00_loc;
+        self.pixel_delta_u = new_views[0].pixel_delta_u;
+        self.pixel_delta_v = new_views[0].pixel_delta_v;
+
+        for (i, view) in new_views.into_iter().enumerate() {
+            *self.pixel_bufs[i].lock().expect("pixel_buf mutex") =
+                vec![0_u8; 4 * view.width * view.height];
+            self.view_xs[i] = view.eye_x + view.view_x;
+            self.view_widths[i] = view.width;
+            self.pixels_rendered[i].store(0, Ordering::Relaxed);
+
+            let mut view = view;
+            view.pixels_rendered = Arc::clone(&self.pixels_rendered[i]);
+            *self.views[i].lock().expect("view mutex") = view;
+        }
+
+        self.image_width = image_width;
+        self.image_height = image_height;
+        self.passes_wanted = 0;
+    }
+
+    pub fn set_options(&mut self, options: CameraOptions) {
+        // Replace every option, rebuilding the views like `resize`.
+        assert!(options.stereo.is_none() || self.views.len() > 1);
+        assert!(options.render_mode.is_valid());
+        let image_width = options.image_width;
+        self.options = options;
+        self.resize(image_width);
+    }
+
+    pub fn set_scene(&mut self, scene: &Arc<Scene>) {
+        // Render another scene from the next render request, restarting accumulation.
+        self.scene = Arc::clone(scene);
+        self.update_views(true, |view| view.scene = Arc::clone(scene));
+    }
+
+    pub fn set_max_depth(&mut self, max_depth: u16) {
+        self.options.max_depth = max_depth;
+        self.update_views(true, |view| view.max_depth = max_depth);
+    }
+
+    pub fn set_samples_per_pass(&mut self, samples_per_pass: u16) {
+        // Only changes how quickly samples accumulate, so the samples so far are kept.
+        self.options.samples_per_pass = samples_per_pass;
+        self.update_views(false, |view| {
+            view.samples_per_pass = samples_per_pass.max(1)
+        });
+    }
+
+    pub fn set_gamma(&mut self, gamma: f64) {
+        self.options.gamma = gamma;
+        self.update_views(true, |view| view.gamma = gamma);
+    }
+
+    pub fn set_bbox_overlay(&mut self, bbox_overlay: bool) {
+        // Only changes what's drawn over the samples, so it's shown right away, even if paused.
+        self.options.bbox_overlay = bbox_overlay;
+        self.update_views(false, |view| view.bbox_overlay = bbox_overlay);
+    }
+
+    fn update_views<F: Fn(&mut View)>(&mut self, restart: bool, f: F) {
+        // View threads only hold their view's lock while rendering, so this takes effect from
+        // the next render request. Restarting discards the accumulated samples of every view.
+        self.finish_steps();
+        for (view, pixel_buf) in self.views.iter().zip(&self.pixel_bufs) {
+            let mut view = view.lock().expect("view mutex");
+            f(&mut view);
+            if restart {
+                view.restart();
+            }
+            view.copy_pixels(&self.scene, &mut pixel_buf.lock().expect("pixel_buf mutex"));
+        }
+
+        if restart {
+            self.passes_wanted = 0;
+        }
+    }
+
+    pub fn set_region(&mut self, x0: usize, y0: usize, x1: usize, y1: usize) {
+        // Renders only the pixels from x0, y0 up to x1, y1 from now on, starting again from the
+        // first pass there, and keeps the pixels outside it as they are.
+        self.finish_steps();
+        let x1 = x1.min(self.image_width);
+        let y1 = y1.min(self.image_height);
+        let y0 = y0.min(y1);
+
+        for (i, view) in self.views.iter().enumerate() {
+            let view_x = self.view_xs[i];
+            let view_width = self.view_widths[i];
+            let view_x0 = x0.saturating_sub(view_x).min(view_width);
+            let view_x1 = x1.saturating_sub(view_x).min(view_width).max(view_x0);
+
+            let mut view = view.lock().expect("view mutex");
+            view.set_region((view_x0, y0, view_x1, y1));
+            view.copy_pixels(
+                &self.scene,
+                &mut self.pixel_bufs[i].lock().expect("pixel_buf mutex"),
+            );
+        }
+
+        self.passes_wanted = 0;
+    }
+
+    pub fn clear_region(&mut self) {
+        // Renders the whole image again.
+        self.set_region(0, 0, self.image_width, self.image_height);
+    }
+
     pub fn get_width(&self) -> usize {
         self.image_width
     }
@@ -178,6 +571,25 @@ impl Camera {
         self.image_height
     }
 
+    pub fn pixel_ray(&self, x: usize, y: usize) -> Ray {
+        // The ray through the center of pixel x, y from the center of the lens, e.g. for picking.
+        let pixel_center =
+            self.pixel00_loc + (x as f64 * self.pixel_delta_u) + (y as f64 * self.pixel_delta_v);
+        Ray::new(self.center, pixel_center - self.center)
+    }
+
+    pub fn project(&self, p: Vec3) -> Option<(f64, f64)> {
+        // Inverse of `pixel_ray`: where the point `p` appears in the image, in pixels, with pixel
+        // centers at whole numbers. `None` if it's not in front of the camera.
+        project(
+            self.center,
+            self.pixel00_loc,
+            self.pixel_delta_u,
+            self.pixel_delta_v,
+            p,
+        )
+    }
+
     pub fn for_each_view<F: FnMut(usize, usize, usize, &[u8])>(&self, mut f: F) {
         for (i, ((view_x, view_width), pixel_buf)) in self
             .view_xs
@@ -192,12 +604,78 @@ impl Camera {
         }
     }
 
+    pub fn edge_mask(&self, min_normal_cos: f64) -> Vec<bool> {
+        // Marks the pixels on geometry edges, row by row from the top, by tracing each pixel's
+        // center ray like `pixel_ray`. A pixel is an edge if a neighbor above, below, left or
+        // right of it hits a different object, or none, or hits at a normal whose cosine with
+        // this pixel's normal is below `min_normal_cos`.
+        let mut first_hits: Vec<Option<(ObjectId, Vec3)>> =
+            vec![None; self.image_width * self.image_height];
+
+        for (view, view_x) in self.views.iter().zip(self.view_xs.iter().copied()) {
+            let view = view.lock().expect("view mutex");
+            for y in 0..view.height {
+                for x in 0..view.width {
+                    let pixel_center = view.pixel00_loc
+                        + (x as f64 * view.pixel_delta_u)
+                        + (y as f64 * view.pixel_delta_v);
+                    let ray = Ray::new(view.center, pixel_center - view.center);
+                    first_hits[y * self.image_width + view_x + x] = self
+                        .scene
+                        .hit(&ray, self.options.ray_epsilon, f64::INFINITY)
+                        .map(|rec| (rec.object_id, rec.normal));
+                }
+            }
+        }
+
+        let is_edge = |a: Option<(ObjectId, Vec3)>, b: Option<(ObjectId, Vec3)>| match (a, b) {
+            (Some((a_id, a_normal)), Some((b_id, b_normal))) => {
+                a_id != b_id || a_normal.dot(b_normal) < min_normal_cos
+            }
+            (None, None) => false,
+            _ => true,
+        };
+
+        let (w, h) = (self.image_width, self.image_height);
+        (0..w * h)
+            .map(|i| {
+                let (x, y) = (i % w, i / w);
+                let hit = first_hits[i];
+                (x > 0 && is_edge(hit, first_hits[i - 1]))
+                    || (x + 1 < w && is_edge(hit, first_hits[i + 1]))
+                    || (y > 0 && is_edge(hit, first_hits[i - w]))
+                    || (y + 1 < h && is_edge(hit, first_hits[i + w]))
+            })
+            .collect()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        // `passes_wanted` only goes past a pass once every view thread has finished it.
+        self.options
+            .max_passes
+            .is_some_and(|max_passes| self.passes_wanted > max_passes)
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        // Unlike `self.pause`, which cuts each render short at its deadline, this stops `render`
+        // from requesting any work at all; accumulated passes are kept for when it's unpaused.
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
     pub fn render(&mut self, until: Instant) {
+        // Don't request any more passes once complete; the view threads just idle in `recv`.
+        if self.paused || self.is_complete() {
+            return;
+        }
+        self.finish_steps();
+
         // Request no more than `self.passes_wanted` render passes from view threads.
-        for passes_wanted_tx in &self.passes_wanted_txs {
-            passes_wanted_tx
-                .send(self.passes_wanted)
-                .expect("passes_wanted_tx");
+        for view_worker in &self.view_workers {
+            view_worker.request(self.passes_wanted);
         }
 
         // Sleep up to `until`, then pause any currently-rendering view threads.
@@ -224,121 +702,1957 @@ impl Camera {
         // Prepare to let view threads render again.
         self.pause.store(false, Ordering::Release);
     }
-}
 
-struct View {
-    color_buf: Vec<Color>,
-    width: usize,
-    height: usize,
-    max_depth: u16,
-    start_row: usize,
-    render_passes: usize,
-    pause: Arc<AtomicBool>,
-    pixel00_loc: Vec3,
-    pixel_delta_u: Vec3,
-    pixel_delta_v: Vec3,
-    center: Vec3,
-    defocus_angle: f64,
-    defocus_disk_u: Vec3,
-    defocus_disk_v: Vec3,
-}
+    pub fn render_for(&mut self, duration: Duration) -> usize {
+        // Renders as many passes as possible within `duration`, unlike `render`, which only works
+        // on the current pass. Returns the number of passes every view has finished.
+        if self.paused || self.is_complete() {
+            return self.passes_wanted.saturating_sub(1);
+        }
+        self.finish_steps();
 
-impl View {
-    fn sample_square(rng: &mut Rng) -> Vec3 {
-        // Returns the vector to a random point in the [-0.5,-0.5] to [+0.5,+0.5] unit square.
-        Vec3::new(rng.random_f64() - 0.5, rng.random_f64() - 0.5, 0.0)
-    }
+        let until = Instant::now() + duration;
+        let passes = self.options.max_passes.unwrap_or(usize::MAX);
 
-    fn defocus_disk_sample(&self, rng: &mut Rng) -> Vec3 {
-        // Returns a random point in the camera defocus disk.
-        let p = Vec3::random_in_unit_disk(rng);
-        self.center + p.x() * self.defocus_disk_u + p.y() * self.defocus_disk_v
+        // Keep requesting passes until they're all done, or pause every view thread at `until`.
+        let passes_done = loop {
+            for view_worker in &self.view_workers {
+                view_worker.request(passes);
+            }
+
+            let mut passes_done = usize::MAX;
+            for passes_done_rx in &self.passes_done_rxs {
+                let timeout = until.saturating_duration_since(Instant::now());
+                let this_passes_done = match passes_done_rx.recv_timeout(timeout) {
+                    Ok(passes_done) => passes_done,
+                    Err(RecvTimeoutError::Timeout) => {
+                        self.pause.store(true, Ordering::Release);
+                        passes_done_rx.recv().expect("passes_done_rx")
+                    }
+                    Err(RecvTimeoutError::Disconnected) => panic!("passes_done_rx"),
+                };
+                passes_done = passes_done.min(this_passes_done);
+            }
+
+            if passes_done >= passes || Instant::now() >= until {
+                break passes_done;
+            }
+        };
+
+        // Prepare to let view threads render again.
+        self.pause.store(false, Ordering::Release);
+
+        self.passes_wanted = usize::max(self.passes_wanted, passes_done + 1);
+        self.passes_wanted - 1
     }
 
-    fn get_ray(&self, rng: &mut Rng, i: f64, j: f64) -> Ray {
-        // Construct a camera ray originating from the defocus disk and directed at a
-        // randomly-sampled point around the pixel location i, j.
+    pub fn try_render_step(&mut self) -> RenderStatus {
+        // Like `render`, but returns right away instead of waiting for a deadline, for callers
+        // with their own event loop. Each call requests the current pass from views that aren't
+        // busy with it, and collects the views that have finished, moving on to the next pass
+        // once every view has. Views render whole passes, so call this again, e.g. every frame,
+        // until it's completed.
+        if self.is_complete() {
+            return RenderStatus::Completed;
+        }
+        if self.paused {
+            return RenderStatus::InProgress;
+        }
 
-        let offset = Self::sample_square(rng);
-        let pixel_sample = self.pixel00_loc
-            + ((i + offset.x()) * self.pixel_delta_u)
-            + ((j + offset.y()) * self.pixel_delta_v);
+        for (i, view_worker) in self.view_workers.iter().enumerate() {
+            if !self.steps_in_flight[i] && self.step_passes_done[i] < self.passes_wanted {
+                view_worker.request(self.passes_wanted);
+                self.steps_in_flight[i] = true;
+            }
+        }
 
-        let ray_origin = if self.defocus_angle <= 0.0 {
-            self.center
+        for (i, passes_done_rx) in self.passes_done_rxs.iter().enumerate() {
+            if self.steps_in_flight[i] {
+                match passes_done_rx.try_recv() {
+                    Ok(passes_done) => {
+                        self.steps_in_flight[i] = false;
+                        self.step_passes_done[i] = passes_done;
+                    }
+                    Err(TryRecvError::Empty) => {}
+                    Err(TryRecvError::Disconnected) => panic!("passes_done_rx"),
+                }
+            }
+        }
+
+        if self
+            .step_passes_done
+            .iter()
+            .all(|&passes_done| passes_done >= self.passes_wanted)
+        {
+            self.passes_wanted += 1;
+        }
+
+        if self.is_complete() {
+            RenderStatus::Completed
         } else {
-            self.defocus_disk_sample(rng)
-        };
-        let ray_direction = pixel_sample - ray_origin;
+            RenderStatus::InProgress
+        }
+    }
 
-        Ray {
-            pos: ray_origin,
-            dir: ray_direction,
+    fn finish_steps(&mut self) {
+        // Waits for the views still rendering passes requested by `try_render_step`, so the next
+        // request to each view is the only one it answers. Views keep the passes they've done, so
+        // forgetting how many there were here only makes `try_render_step` ask them again.
+        for (in_flight, passes_done_rx) in
+            self.steps_in_flight.iter_mut().zip(&self.passes_done_rxs)
+        {
+            if *in_flight {
+                passes_done_rx.recv().expect("passes_done_rx");
+                *in_flight = false;
+            }
         }
+        self.step_passes_done.fill(0);
     }
 
-    fn ray_color(rng: &mut Rng, depth: u16, r: &Ray, scene: &Scene) -> Color {
-        if depth == 0 {
-            return Color::new(0.0, 0.0, 0.0);
+    pub fn pixels_rendered(&self) -> usize {
+        // Total pixels rendered by every view over all passes, updated as each row is finished.
+        self.pixels_rendered
+            .iter()
+            .map(|p| p.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    pub fn sample_buffer_bytes(&self) -> usize {
+        // Memory taken by the sums of samples of every view, which `f32-accum` halves.
+        self.views
+            .iter()
+            .map(|view| {
+                let view = view.lock().expect("view mutex");
+                size_of_val(&view.color_buf[..])
+                    + size_of_val(&view.alpha_buf[..])
+                    + size_of_val(&view.weight_buf[..])
+                    + size_of_val(&view.square_buf[..])
+            })
+            .sum()
+    }
+
+    pub fn estimated_variance(&self) -> f64 {
+        // The variance of each pixel's luminance as an estimate of its true value, averaged over
+        // the image, which falls as samples accumulate; e.g. stop rendering once it's small
+        // enough. Weights are taken as sample counts, which is exact for the box filter and
+        // overestimates the variance for the tent filter. Pixels need two samples for an
+        // estimate, and it's infinite until any have them.
+        let mut total = 0.0;
+        let mut pixels = 0;
+        for view in &self.views {
+            let view = view.lock().expect("view mutex");
+            let sums = view.color_buf.iter().zip(&view.weight_buf);
+            for ((c, w), sq) in sums.zip(&view.square_buf) {
+                let weight = accum_to_f64(*w);
+                if weight <= 1.0 {
+                    continue;
+                }
+                // The samples' unbiased variance over the sample count.
+                let mean = accum_color(*c).luminance() / weight;
+                let mean_sq = accum_to_f64(*sq) / weight;
+                total += (mean_sq - mean * mean).max(0.0) / (weight - 1.0);
+                pixels += 1;
+            }
         }
 
-        if let Some(rec) = scene.hit(r, 0.001, f64::INFINITY) {
-            return if let Some(sc_rec) = rec.mat.scatter(rng, r, &rec) {
-                sc_rec.attenuation * Self::ray_color(rng, depth - 1, &sc_rec.scattered, scene)
-            } else {
-                Color::new(0.0, 0.0, 0.0)
-            };
+        if pixels > 0 {
+            total / pixels as f64
+        } else {
+            f64::INFINITY
         }
+    }
 
-        let unit_direction = r.dir.unit();
-        let a = 0.5 * (unit_direction.y() + 1.0);
-        (1.0 - a) * Color::new(1.0, 1.0, 1.0) + a * Color::new(0.5, 0.7, 1.0)
+    pub fn render_to_completion(&mut self, passes: usize) {
+        self.render_to_completion_with_progress(passes, None);
     }
 
-    pub fn render(
-        &mut self,
-        rng: &mut Rng,
-        scene: &Scene,
-        pixel_buf: &mut [u8],
-        passes_wanted: usize,
-    ) {
-        if self.render_passes >= passes_wanted {
-            return;
+    pub fn render_single_threaded(&mut self, passes: usize) {
+        // Like `render_to_completion`, but renders each view in turn on the calling thread, in
+        // pixel order, e.g. to step through a pixel in a debugger. Every pixel's samples are
+        // seeded from its position and pass, so the image is the same as with view threads.
+        self.finish_steps();
+        for (view, pixel_buf) in self.views.iter().zip(&self.pixel_bufs) {
+            let mut view = view.lock().expect("view mutex");
+            while view.render_passes < passes {
+                view.render(&self.scene, passes);
+            }
+            view.copy_pixels(&self.scene, &mut pixel_buf.lock().expect("pixel_buf mutex"));
         }
 
-        let passes_plus_one = self.render_passes as f64 + 1.0;
-        let mut color_buf = vec![];
+        self.passes_wanted = usize::max(self.passes_wanted, passes + 1);
+    }
 
-        std::mem::swap(&mut color_buf, &mut self.color_buf);
+    pub fn render_to_completion_with_progress(&mut self, passes: usize, label: Option<&str>) {
+        // Like `render_to_completion`, but if `label` is given, also shows it along with the
+        // progress made on stderr a few times a second, then the time taken when done.
+        self.finish_steps();
+        let start = Instant::now();
+        let mut last_report: Option<Instant> = None;
 
-        let color_rows = color_buf.chunks_exact_mut(self.width);
-        let pixel_rows = pixel_buf.chunks_exact_mut(self.width * 4);
+        // Keep requesting passes without pausing until every view thread has done them all.
+        loop {
+            for view_worker in &self.view_workers {
+                view_worker.request(passes);
+            }
 
-        for (y, (color_row, pixel_row)) in
-            color_rows.zip(pixel_rows).enumerate().skip(self.start_row)
-        {
-            let colors = color_row.iter_mut();
-            let pixels = pixel_row.chunks_exact_mut(4);
+            let mut all_passes_done = true;
+            for passes_done_rx in &self.passes_done_rxs {
+                let this_passes_done = loop {
+                    match passes_done_rx.recv_timeout(Duration::from_millis(50)) {
+                        Ok(passes_done) => break passes_done,
+                        Err(RecvTimeoutError::Timeout) => {}
+                        Err(RecvTimeoutError::Disconnected) => panic!("passes_done_rx"),
+                    }
 
-            for (x, (c, p)) in colors.zip(pixels).enumerate() {
-                let ray = self.get_ray(rng, x as f64, y as f64);
-                *c += Self::ray_color(rng, self.max_depth, &ray, scene);
-                p[0] = ((c.r() / passes_plus_one).sqrt() * 255.999) as u8;
-                p[1] = ((c.g() / passes_plus_one).sqrt() * 255.999) as u8;
-                p[2] = ((c.b() / passes_plus_one).sqrt() * 255.999) as u8;
-                p[3] = 255;
+                    if let Some(label) = label
+                        && last_report.is_none_or(|t| t.elapsed() >= Duration::from_millis(250))
+                    {
+                        self.report_progress(label, passes);
+                        last_report = Some(Instant::now());
+                    }
+                };
+                if this_passes_done < passes {
+                    all_passes_done = false;
+                }
             }
 
-            self.start_row += 1;
-            if self.start_row >= self.height {
-                self.render_passes += 1;
-                self.start_row = 0;
-            }
-            if self.pause.load(Ordering::Acquire) {
+            if all_passes_done {
                 break;
             }
         }
 
-        std::mem::swap(&mut color_buf, &mut self.color_buf);
+        if let Some(label) = label {
+            self.report_progress(label, passes);
+            eprintln!(" in {:.1?}", start.elapsed());
+        }
+
+        self.passes_wanted = usize::max(self.passes_wanted, passes + 1);
+    }
+
+    fn report_progress(&self, label: &str, passes: usize) {
+        let frame_pixels = self.image_width * self.image_height;
+        let total_pixels = (passes * frame_pixels).max(1);
+        let pixels_rendered = self.pixels_rendered().min(total_pixels);
+        eprint!(
+            "\r{label}pass {}/{passes} ({:.1}%)",
+            (pixels_rendered / frame_pixels + 1).min(passes),
+            100.0 * pixels_rendered as f64 / total_pixels as f64,
+        );
+    }
+
+    pub fn save_checkpoint<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        // Saves the accumulated samples and progress of every view, so a render can be resumed
+        // with `load_checkpoint` by a camera with the same options. Samples are seeded from the
+        // RNG seed, pass number and pixel position, so the seed is all the RNG state needed.
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+        file.write_all(CHECKPOINT_MAGIC)?;
+        write_u64(&mut file, self.rng_seed)?;
+        write_u64(&mut file, self.image_width as u64)?;
+        write_u64(&mut file, self.image_height as u64)?;
+        write_u64(&mut file, self.views.len() as u64)?;
+
+        for (view, view_x) in self.views.iter().zip(&self.view_xs) {
+            let view = view.lock().expect("view mutex");
+            write_u64(&mut file, *view_x as u64)?;
+            write_u64(&mut file, view.width as u64)?;
+            write_u64(&mut file, view.height as u64)?;
+            write_u64(&mut file, view.render_passes as u64)?;
+            write_u64(&mut file, view.start_row as u64)?;
+            let samples = view.color_buf.iter().zip(&view.alpha_buf);
+            let samples = samples.zip(&view.weight_buf).zip(&view.square_buf);
+            for (((c, a), w), sq) in samples {
+                for x in [c[0], c[1], c[2], *a, *w, *sq] {
+                    write_f64(&mut file, accum_to_f64(x))?;
+                }
+            }
+        }
+
+        file.flush()
+    }
+
+    pub fn load_checkpoint<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<()> {
+        // Restores what `save_checkpoint` saved, rejecting checkpoints of a different layout.
+        // Nothing is changed unless the whole checkpoint is read successfully.
+        let mismatch = |what: &str| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("checkpoint {what} doesn't match camera"),
+            )
+        };
+
+        let mut file = std::io::BufReader::new(std::fs::File::open(path)?);
+        self.finish_steps();
+
+        let mut magic = [0_u8; 8];
+        file.read_exact(&mut magic)?;
+        if &magic != CHECKPOINT_MAGIC {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "not a render checkpoint",
+            ));
+        }
+
+        let rng_seed = read_u64(&mut file)?;
+        if read_u64(&mut file)? != self.image_width as u64
+            || read_u64(&mut file)? != self.image_height as u64
+        {
+            return Err(mismatch("image size"));
+        }
+        if read_u64(&mut file)? != self.views.len() as u64 {
+            return Err(mismatch("view count"));
+        }
+
+        let mut loaded = vec![];
+        for (view, view_x) in self.views.iter().zip(&self.view_xs) {
+            let view = view.lock().expect("view mutex");
+            if read_u64(&mut file)? != *view_x as u64
+                || read_u64(&mut file)? != view.width as u64
+                || read_u64(&mut file)? != view.height as u64
+            {
+                return Err(mismatch("view layout"));
+            }
+
+            let render_passes = read_u64(&mut file)? as usize;
+            let start_row = read_u64(&mut file)? as usize;
+            if start_row >= view.height {
+                return Err(mismatch("view progress"));
+            }
+
+            let mut color_buf = Vec::with_capacity(view.color_buf.len());
+            let mut alpha_buf = Vec::with_capacity(view.alpha_buf.len());
+            let mut weight_buf = Vec::with_capacity(view.weight_buf.len());
+            let mut square_buf = Vec::with_capacity(view.square_buf.len());
+            for _ in 0..view.color_buf.len() {
+                let r = read_f64(&mut file)? as Accum;
+                let g = read_f64(&mut file)? as Accum;
+                let b = read_f64(&mut file)? as Accum;
+                color_buf.push([r, g, b]);
+                alpha_buf.push(read_f64(&mut file)? as Accum);
+                weight_buf.push(read_f64(&mut file)? as Accum);
+                square_buf.push(read_f64(&mut file)? as Accum);
+            }
+
+            let sums = (color_buf, alpha_buf, weight_buf, square_buf);
+            loaded.push((render_passes, start_row, sums));
+        }
+
+        let mut passes_done = usize::MAX;
+        for (i, (render_passes, start_row, sums)) in loaded.into_iter().enumerate() {
+            let mut view = self.views[i].lock().expect("view mutex");
+            // Keep the offset of the right eye's seed in stereo renders.
+            view.rng_seed = rng_seed.wrapping_add(view.rng_seed.wrapping_sub(self.rng_seed));
+            view.render_passes = render_passes;
+            view.start_row = start_row;
+            (
+                view.color_buf,
+                view.alpha_buf,
+                view.weight_buf,
+                view.square_buf,
+            ) = sums;
+            view.write_pixels();
+
+            self.pixels_rendered[i].store(
+                (render_passes * view.height + start_row) * view.width,
+                Ordering::Relaxed,
+            );
+            view.copy_pixels(
+                &self.scene,
+                &mut self.pixel_bufs[i].lock().expect("pixel_buf mutex"),
+            );
+
+            passes_done = passes_done.min(render_passes);
+        }
+
+        // Pick up from the first pass that isn't finished by every view.
+        self.rng_seed = rng_seed;
+        self.passes_wanted = passes_done;
+
+        Ok(())
+    }
+
+    pub fn frame_linear(&self) -> Vec<Color> {
+        // The whole image as the average of each pixel's samples so far, rows from top to bottom,
+        // before it's clamped and gamma encoded, e.g. for the functions in `post`. Pixels without
+        // any samples are black.
+        let mut frame = vec![Color::new(0.0, 0.0, 0.0); self.image_width * self.image_height];
+        for (view, view_x) in self.views.iter().zip(self.view_xs.iter().copied()) {
+            let view = view.lock().expect("view mutex");
+            let sums = view.color_buf.iter().zip(&view.weight_buf);
+            for (i, (c, w)) in sums.enumerate() {
+                let weight = accum_to_f64(*w);
+                if weight > 0.0 {
+                    let (x, y) = (i % view.width, i / view.width);
+                    frame[y * self.image_width + view_x + x] = (1.0 / weight) * accum_color(*c);
+                }
+            }
+        }
+        frame
+    }
+
+    pub fn frame_rgba8(&self) -> Vec<u8> {
+        // The whole image as RGBA bytes, rows from top to bottom, for exporting or display. Any
+        // `post_effects` are applied to the linear image first, which covers up the overlay.
+        let row_len = 4 * self.image_width;
+        let mut frame = vec![0_u8; row_len * self.image_height];
+
+        self.for_each_view(|_, view_x, view_width, pixel_buf| {
+            let frame_rows = frame.chunks_exact_mut(row_len);
+            let view_rows = pixel_buf.chunks_exact(4 * view_width);
+
+            for (frame_row, view_row) in frame_rows.zip(view_rows) {
+                frame_row[4 * view_x..4 * (view_x + view_width)].copy_from_slice(view_row);
+            }
+        });
+
+        if !self.options.post_effects.is_empty() {
+            let (width, height) = (self.image_width, self.image_height);
+            let color = self
+                .options
+                .post_effects
+                .iter()
+                .fold(self.frame_linear(), |color, effect| {
+                    effect.apply(color, width, height)
+                });
+            for (p, c) in frame.chunks_exact_mut(4).zip(color) {
+                let c = c.clamp01();
+                p[0] = (View::gamma_encode(c.r(), self.options.gamma) * 255.999) as u8;
+                p[1] = (View::gamma_encode(c.g(), self.options.gamma) * 255.999) as u8;
+                p[2] = (View::gamma_encode(c.b(), self.options.gamma) * 255.999) as u8;
+            }
+        }
+
+        frame
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        png::write_rgba8(
+            file,
+            self.image_width as u32,
+            self.image_height as u32,
+            &self.frame_rgba8(),
+        )
+    }
+
+    pub fn save_bmp<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        bmp::write_rgba8(
+            file,
+            self.image_width as u32,
+            self.image_height as u32,
+            &self.frame_rgba8(),
+        )
+    }
+}
+
+fn project(
+    center: Vec3,
+    pixel00_loc: Vec3,
+    pixel_delta_u: Vec3,
+    pixel_delta_v: Vec3,
+    p: Vec3,
+) -> Option<(f64, f64)> {
+    // Follows the line from `center` to `p` out to the viewport, then measures where it crosses
+    // from the first pixel's center in pixel steps.
+    let n = pixel_delta_u.cross(pixel_delta_v);
+    let s = n.dot(pixel00_loc - center) / n.dot(p - center);
+    if !(s > 0.0 && s.is_finite()) {
+        return None;
+    }
+    let q = center + s * (p - center) - pixel00_loc;
+    Some((
+        q.dot(pixel_delta_u) / pixel_delta_u.length_squared(),
+        q.dot(pixel_delta_v) / pixel_delta_v.length_squared(),
+    ))
+}
+
+#[derive(Copy, Clone, Default)]
+struct PathState<'a> {
+    sampled_from: Option<&'a HitRecord<'a>>, // Where lights were sampled, if scattered off it
+    media: Media,                            // Dielectrics the ray is inside of
+}
+
+fn balance_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    // Weight of a sample taken with density `pdf` when another strategy could have taken it with
+    // `other_pdf`, so that the weights of both add up to one.
+    if pdf > 0.0 {
+        pdf / (pdf + other_pdf)
+    } else {
+        0.0
+    }
+}
+
+const BBOX_OVERLAY_COLOR: [u8; 4] = [255, 255, 0, 255];
+
+// Sums of samples are kept as `f32` with the `f32-accum` feature, halving the memory of each
+// view's buffers for a little less precision, which 8-bit pixels rarely show.
+#[cfg(not(feature = "f32-accum"))]
+type Accum = f64;
+#[cfg(feature = "f32-accum")]
+type Accum = f32;
+
+#[allow(clippy::unnecessary_cast)] // Only unnecessary without the feature
+fn accum_to_f64(x: Accum) -> f64 {
+    x as f64
+}
+
+fn accum_color(c: [Accum; 3]) -> Color {
+    Color::new(accum_to_f64(c[0]), accum_to_f64(c[1]), accum_to_f64(c[2]))
+}
+
+const CHECKPOINT_MAGIC: &[u8; 8] = b"RTCHKPT3";
+
+fn write_u64<W: Write>(w: &mut W, x: u64) -> std::io::Result<()> {
+    w.write_all(&x.to_le_bytes())
+}
+
+fn write_f64<W: Write>(w: &mut W, x: f64) -> std::io::Result<()> {
+    w.write_all(&x.to_le_bytes())
+}
+
+fn read_u64<R: Read>(r: &mut R) -> std::io::Result<u64> {
+    let mut bytes = [0_u8; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f64<R: Read>(r: &mut R) -> std::io::Result<f64> {
+    let mut bytes = [0_u8; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
+
+impl Drop for Camera {
+    fn drop(&mut self) {
+        // Hanging up makes each view thread's `recv` fail, so they all exit their loops, once
+        // they aren't waiting to say they're done.
+        self.finish_steps();
+        self.view_workers.clear();
+
+        for view_thread in self.view_threads.drain(..) {
+            // A view thread that panicked has already been reported; don't panic again here.
+            let _ = view_thread.join();
+        }
+    }
+}
+
+fn morton_code(x: usize, y: usize) -> usize {
+    // Interleaves the bits of `x` and `y`, so sorting points by it visits them in Z-order.
+    (0..usize::BITS as usize / 2).fold(0, |code, bit| {
+        code | (x >> bit & 1) << (2 * bit) | (y >> bit & 1) << (2 * bit + 1)
+    })
+}
+
+struct View {
+    scene: Arc<Scene>,
+    color_buf: Vec<[Accum; 3]>,
+    alpha_buf: Vec<Accum>, // Weighted sums of sample alphas, like `color_buf`
+    weight_buf: Vec<Accum>,
+    square_buf: Vec<Accum>, // Weighted sums of squared sample luminances, for noise estimates
+    pixel_buf: Vec<u8>,
+    width: usize,
+    height: usize,
+    region: (usize, usize, usize, usize), // Pixels rendered, from x0, y0 up to x1, y1
+    max_depth: u16,
+    ray_epsilon: f64,
+    max_tests: Option<usize>,
+    samples_per_pass: u16,
+    pixel_filter: PixelFilter,
+    pixel_order: PixelOrder,
+    gamma: f64,
+    render_mode: RenderMode,
+    bbox_overlay: bool,
+    start_row: usize,
+    render_passes: usize,
+    pixels_rendered: Arc<AtomicUsize>, // Pixels rendered over all passes, for progress reports
+    rng_seed: u64,
+    eye_x: usize,  // Left edge of this view's eye in the whole image, for stereo renders
+    view_x: usize, // Left edge of this view within its eye
+    image_width: usize, // Width of each eye's image
+    pause: Arc<AtomicBool>,
+    pixel00_loc: Vec3,
+    pixel_delta_u: Vec3,
+    pixel_delta_v: Vec3,
+    center: Vec3,
+    defocus_angle: f64,
+    aperture_blades: u8,
+    background: Background,
+    sky_intensity: f64,
+    alpha_background: bool,
+    override_material: Option<Arc<Material>>,
+    defocus_disk_u: Vec3,
+    defocus_disk_v: Vec3,
+}
+
+impl View {
+    fn sample_square(rng: &mut Rng) -> Vec3 {
+        // Returns the vector to a random point in the [-0.5,-0.5] to [+0.5,+0.5] unit square.
+        Vec3::new(rng.random_f64() - 0.5, rng.random_f64() - 0.5, 0.0)
+    }
+
+    fn filter_sample(&self, rng: &mut Rng) -> (Vec3, f64) {
+        // Returns a random offset from the pixel center and the weight of a sample taken there.
+        match self.pixel_filter {
+            PixelFilter::Box => (Self::sample_square(rng), 1.0),
+            PixelFilter::Tent => {
+                let offset = Vec3::new(
+                    rng.random_f64_range(-1.0, 1.0),
+                    rng.random_f64_range(-1.0, 1.0),
+                    0.0,
+                );
+                let weight = (1.0 - offset.x().abs()) * (1.0 - offset.y().abs());
+                (offset, weight)
+            }
+        }
+    }
+
+    fn aperture_polygon_sample(rng: &mut Rng, sides: u8) -> Vec3 {
+        // Returns a random point in a regular polygon inscribed in the unit circle, by picking
+        // one of its equal-area triangular slices, then a uniform point within that slice.
+        let slice_angle = 2.0 * std::f64::consts::PI / sides as f64;
+        let slice = rng.random_u64(sides as u64) as f64;
+        let a0 = slice * slice_angle;
+        let a1 = a0 + slice_angle;
+        let v0 = Vec3::new(a0.cos(), a0.sin(), 0.0);
+        let v1 = Vec3::new(a1.cos(), a1.sin(), 0.0);
+
+        let s = rng.random_f64().sqrt();
+        let t = rng.random_f64();
+        s * ((1.0 - t) * v0 + t * v1)
+    }
+
+    fn defocus_disk_sample(&self, rng: &mut Rng) -> Vec3 {
+        // Returns a random point in the camera defocus disk, or polygonal aperture if set.
+        let p = if self.aperture_blades > 2 {
+            Self::aperture_polygon_sample(rng, self.aperture_blades)
+        } else {
+            Vec3::random_in_unit_disk(rng)
+        };
+        self.center + p.x() * self.defocus_disk_u + p.y() * self.defocus_disk_v
+    }
+
+    fn get_ray(&self, rng: &mut Rng, i: f64, j: f64, offset: Vec3) -> Ray {
+        // Construct a camera ray originating from the defocus disk and directed at the point
+        // `offset` away from the pixel location i, j.
+
+        let pixel_sample = self.pixel00_loc
+            + ((i + offset.x()) * self.pixel_delta_u)
+            + ((j + offset.y()) * self.pixel_delta_v);
+
+        let ray_origin = if self.defocus_angle <= 0.0 {
+            self.center
+        } else {
+            self.defocus_disk_sample(rng)
+        };
+        let ray_direction = pixel_sample - ray_origin;
+
+        Ray::new(ray_origin, ray_direction)
+    }
+
+    fn miss_color(&self, r: &Ray) -> (Color, f64) {
+        // Color and alpha of camera ray `r` when it misses the scene.
+        if self.alpha_background {
+            (Color::new(0.0, 0.0, 0.0), 0.0)
+        } else {
+            (self.sky_intensity * self.background.color(r.dir), 1.0)
+        }
+    }
+
+    fn light_sample_color(
+        &self,
+        rng: &mut Rng,
+        rec: &HitRecord,
+        albedo: Color,
+        scene: &Scene,
+    ) -> Color {
+        // Light reaching a diffuse surface directly from the scene's lights with a surface, by
+        // sampling directions toward each. Rays scattered off the surface can find the same
+        // light, so both are weighted by the balance heuristic to count it once between them.
+        let mut color = Color::new(0.0, 0.0, 0.0);
+        for light in scene.lights() {
+            let Some((dir, pdf)) = light.sample_toward(rec.p, rng) else {
+                continue;
+            };
+            let cos_theta = rec.normal.dot(dir);
+            if cos_theta <= 0.0 {
+                continue;
+            }
+
+            let shadow_ray = Ray::new(rec.p, dir);
+            let Some(light_rec) = light.hit(&shadow_ray, self.ray_epsilon, f64::INFINITY) else {
+                continue;
+            };
+            if scene.hit_any(
+                &shadow_ray,
+                self.ray_epsilon,
+                light_rec.t - self.ray_epsilon,
+            ) {
+                continue;
+            }
+
+            // A Lambertian surface reflects `albedo / pi` of the light from each direction, and
+            // scatters toward it with a density of `cos_theta / pi`.
+            let emitted =
+                light_rec
+                    .mat
+                    .emitted(light_rec.u, light_rec.v, light_rec.p, light_rec.front_face);
+            let brdf_pdf = cos_theta / std::f64::consts::PI;
+            let weight = balance_heuristic(pdf, brdf_pdf);
+            color += (weight * cos_theta / (std::f64::consts::PI * pdf)) * (albedo * emitted);
+        }
+
+        // The sun of a daylight sky is sampled the same way, though it isn't one of the scene's
+        // lights; nothing at all can be in the way of it.
+        if let Background::Sky(sky) = &self.background
+            && sky.has_sun_disk()
+        {
+            let (dir, pdf, radiance) = sky.sample_sun(rec.p, rng);
+            let cos_theta = rec.normal.dot(dir);
+            let shadow_ray = Ray::new(rec.p, dir);
+            if cos_theta > 0.0 && !scene.hit_any(&shadow_ray, self.ray_epsilon, f64::INFINITY) {
+                let brdf_pdf = cos_theta / std::f64::consts::PI;
+                let weight = balance_heuristic(pdf, brdf_pdf);
+                color += (weight * cos_theta / (std::f64::consts::PI * pdf))
+                    * (albedo * (self.sky_intensity * radiance));
+            }
+        }
+        color
+    }
+
+    fn camera_ray_color(
+        &self,
+        rng: &mut Rng,
+        r: &Ray,
+        hit: Option<HitRecord>,
+        scene: &Scene,
+    ) -> (Color, f64) {
+        // Like `hit_color`, but for the first ray of a path.
+        if hit.is_none() {
+            return self.miss_color(r);
+        }
+        self.hit_color(rng, self.max_depth, r, hit, scene, PathState::default())
+    }
+
+    fn ray_color(
+        &self,
+        rng: &mut Rng,
+        depth: u16,
+        r: &Ray,
+        scene: &Scene,
+        path: PathState,
+    ) -> Color {
+        if depth == 0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let hit = self.scene_hit(r, scene);
+        self.hit_color(rng, depth, r, hit, scene, path).0
+    }
+
+    fn hit_color(
+        &self,
+        rng: &mut Rng,
+        depth: u16,
+        r: &Ray,
+        hit: Option<HitRecord>,
+        scene: &Scene,
+        path: PathState,
+    ) -> (Color, f64) {
+        // Color and alpha of ray `r` given its closest hit, which may have been found as part of
+        // a packet. If `r` was scattered off `path.sampled_from`, where lights were sampled
+        // directly, the emission of those lights is weighted to match.
+        if let Some(mut rec) = hit {
+            let mat = self.override_material.as_ref().unwrap_or(rec.mat);
+            if let Material::ShadowCatcher = **mat {
+                return self.shadow_catcher_color(r, &rec, scene);
+            }
+            rec.normal = mat.shading_normal(&rec);
+
+            let mut emitted = mat.emitted(rec.u, rec.v, rec.p, rec.front_face);
+            if let Some(prev) = path.sampled_from
+                && let Some(light) = scene.object_light(rec.object_id)
+            {
+                let brdf_pdf = prev.normal.dot(r.dir.unit()).max(0.0) / std::f64::consts::PI;
+                let light_pdf = light.pdf_toward(prev.p, r.dir);
+                emitted = balance_heuristic(brdf_pdf, light_pdf) * emitted;
+            }
+            let color =
+                if let Some((sc_rec, media)) = mat.scatter_through(rng, r, &rec, &path.media) {
+                    let sampled = mat.is_diffuse();
+                    let direct = if sampled {
+                        self.light_sample_color(rng, &rec, sc_rec.attenuation, scene)
+                    } else {
+                        Color::new(0.0, 0.0, 0.0)
+                    };
+                    let path = PathState {
+                        sampled_from: sampled.then_some(&rec),
+                        media,
+                    };
+                    emitted
+                        + direct
+                        + sc_rec.attenuation
+                            * self.ray_color(rng, depth - 1, &sc_rec.scattered, scene, path)
+                } else {
+                    emitted
+                };
+            return (color, 1.0);
+        }
+
+        let mut background = self.sky_intensity * self.background.color(r.dir);
+        if let Some(prev) = path.sampled_from
+            && let Background::Sky(sky) = &self.background
+        {
+            let brdf_pdf = prev.normal.dot(r.dir.unit()).max(0.0) / std::f64::consts::PI;
+            background = balance_heuristic(brdf_pdf, sky.sun_pdf(r.dir)) * background;
+        }
+        (background, 1.0)
+    }
+
+    fn scene_hit<'s>(&self, r: &Ray, scene: &'s Scene) -> Option<HitRecord<'s>> {
+        scene
+            .hit_limited(r, self.ray_epsilon, f64::INFINITY, self.max_tests)
+            .0
+    }
+
+    fn in_shadow(&self, p: Vec3, light: &Light, scene: &Scene) -> bool {
+        // Shadow rays reach the light's center at `t` = 1, so anything hit before then blocks
+        // it, other than the surface of a sphere light itself.
+        let to_light = light.pos() - p;
+        let dist = to_light.length();
+        let shadow_tmin = self.ray_epsilon / dist;
+        let shadow_tmax = (dist - light.radius() - self.ray_epsilon) / dist;
+        let shadow_ray = Ray::new(p, to_light);
+        shadow_tmax > shadow_tmin && scene.hit_any(&shadow_ray, shadow_tmin, shadow_tmax)
+    }
+
+    fn shadow_catcher_color(&self, r: &Ray, rec: &HitRecord, scene: &Scene) -> (Color, f64) {
+        // The fraction of the light reaching `rec.p` that's blocked is both how much the
+        // background is darkened and the alpha.
+        let mut total = 0.0;
+        let mut blocked = 0.0;
+        for light in scene.lights() {
+            let cos_theta = rec.normal.dot((light.pos() - rec.p).unit());
+            if cos_theta <= 0.0 {
+                continue;
+            }
+
+            let amount = cos_theta * light.illuminance(rec.p).luminance();
+            total += amount;
+            if self.in_shadow(rec.p, light, scene) {
+                blocked += amount;
+            }
+        }
+
+        let shadow = if total > 0.0 { blocked / total } else { 0.0 };
+        let behind = self.sky_intensity * self.background.color(r.dir);
+        ((1.0 - shadow) * behind, shadow)
+    }
+
+    fn depth_color(&self, r: &Ray, scene: &Scene, near: f64, far: f64) -> (Color, f64) {
+        let Some(rec) = self.scene_hit(r, scene) else {
+            let alpha = if self.alpha_background { 0.0 } else { 1.0 };
+            return (Color::new(alpha, alpha, alpha), alpha);
+        };
+
+        // Camera rays aren't unit length, so scale `t` to get the real distance.
+        let dist = rec.t * r.dir.length();
+        let gray = ((dist - near) / (far - near)).clamp(0.0, 1.0);
+        (Color::new(gray, gray, gray), 1.0)
+    }
+
+    fn ambient_occlusion_color(
+        &self,
+        rng: &mut Rng,
+        r: &Ray,
+        scene: &Scene,
+        radius: f64,
+        samples: u16,
+    ) -> (Color, f64) {
+        let Some(rec) = self.scene_hit(r, scene) else {
+            let alpha = if self.alpha_background { 0.0 } else { 1.0 };
+            return (Color::new(alpha, alpha, alpha), alpha);
+        };
+
+        // Cosine-weighted directions over the hemisphere the normal faces, with unit length so
+        // `radius` bounds `t`.
+        let samples = samples.max(1);
+        let unoccluded = (0..samples)
+            .filter(|_| {
+                let dir = rec.normal + Vec3::random_unit_vector(rng);
+                let dir = if dir.near_zero() {
+                    rec.normal
+                } else {
+                    dir.unit()
+                };
+                let ao_ray = Ray::new_at_time(rec.p, dir, r.time);
+                !scene.hit_any(&ao_ray, self.ray_epsilon, radius)
+            })
+            .count();
+        let gray = unoccluded as f64 / samples as f64;
+        (Color::new(gray, gray, gray), 1.0)
+    }
+
+    fn direct_lighting_color(&self, r: &Ray, scene: &Scene) -> (Color, f64) {
+        let Some(mut rec) = self.scene_hit(r, scene) else {
+            return self.miss_color(r);
+        };
+
+        let mat = self.override_material.as_ref().unwrap_or(rec.mat);
+        if let Material::ShadowCatcher = **mat {
+            return self.shadow_catcher_color(r, &rec, scene);
+        }
+        rec.normal = mat.shading_normal(&rec);
+        let albedo = mat.albedo();
+
+        let color = scene
+            .lights()
+            .iter()
+            .map(|light| {
+                let cos_theta = rec.normal.dot((light.pos() - rec.p).unit());
+                if cos_theta <= 0.0 || self.in_shadow(rec.p, light, scene) {
+                    Color::new(0.0, 0.0, 0.0)
+                } else {
+                    cos_theta * (albedo * light.illuminance(rec.p))
+                }
+            })
+            .sum();
+        (color, 1.0)
+    }
+
+    fn sample_color(&self, rng: &mut Rng, scene: &Scene, x: usize, y: usize) -> (Color, f64, f64) {
+        // Returns the color, alpha and weight of a sample of pixel x, y.
+        let (offset, weight) = self.filter_sample(rng);
+        let ray = self.get_ray(rng, x as f64, y as f64, offset);
+        let (color, alpha) = match self.render_mode {
+            RenderMode::Shaded if self.max_depth == 0 => (Color::new(0.0, 0.0, 0.0), 1.0),
+            RenderMode::Shaded => {
+                let hit = self.scene_hit(&ray, scene);
+                self.camera_ray_color(rng, &ray, hit, scene)
+            }
+            RenderMode::Depth { near, far } => self.depth_color(&ray, scene, near, far),
+            RenderMode::DirectLighting => self.direct_lighting_color(&ray, scene),
+            RenderMode::AmbientOcclusion { radius, samples } => {
+                self.ambient_occlusion_color(rng, &ray, scene, radius, samples)
+            }
+        };
+        (color, alpha, weight)
+    }
+
+    fn sample_colors(
+        &self,
+        rngs: &mut [Rng; 4],
+        scene: &Scene,
+        x0: usize,
+        y: usize,
+        n: usize,
+    ) -> [(Color, f64, f64); 4] {
+        // Samples `n` pixels in a row starting at `x0`; a full set of four shaded pixels has its
+        // camera rays intersected together as a packet, which gives the same result.
+        if n < 4
+            || self.max_depth == 0
+            || self.max_tests.is_some()
+            || !matches!(self.render_mode, RenderMode::Shaded)
+        {
+            return std::array::from_fn(|i| {
+                if i < n {
+                    self.sample_color(&mut rngs[i], scene, x0 + i, y)
+                } else {
+                    (Color::new(0.0, 0.0, 0.0), 0.0, 0.0)
+                }
+            });
+        }
+
+        let mut weights = [0.0; 4];
+        let rays: [Ray; 4] = std::array::from_fn(|i| {
+            let (offset, weight) = self.filter_sample(&mut rngs[i]);
+            weights[i] = weight;
+            self.get_ray(&mut rngs[i], (x0 + i) as f64, y as f64, offset)
+        });
+
+        let mut hits = scene.hit4(&rays, self.ray_epsilon, f64::INFINITY);
+        std::array::from_fn(|i| {
+            let hit = hits[i].take();
+            let (color, alpha) = self.camera_ray_color(&mut rngs[i], &rays[i], hit, scene);
+            (color, alpha, weights[i])
+        })
+    }
+
+    fn gamma_encode(x: f64, gamma: f64) -> f64 {
+        if gamma == 2.0 {
+            x.sqrt()
+        } else {
+            x.powf(1.0 / gamma)
+        }
+    }
+
+    fn write_pixel(p: &mut [u8], color_sum: Color, alpha_sum: f64, weight_sum: f64, gamma: f64) {
+        // With the box filter every weight is one, so this divides by the sample count.
+        let (avg, alpha) = if weight_sum > 0.0 {
+            (
+                (1.0 / weight_sum * color_sum).clamp01(),
+                (alpha_sum / weight_sum).clamp(0.0, 1.0),
+            )
+        } else {
+            (Color::new(0.0, 0.0, 0.0), 1.0)
+        };
+        p[0] = (Self::gamma_encode(avg.r(), gamma) * 255.999) as u8;
+        p[1] = (Self::gamma_encode(avg.g(), gamma) * 255.999) as u8;
+        p[2] = (Self::gamma_encode(avg.b(), gamma) * 255.999) as u8;
+        p[3] = (alpha * 255.999) as u8;
+    }
+
+    fn write_pixels(&mut self) {
+        // Regenerates the whole pixel buffer from the accumulated samples.
+        let pixels = self.pixel_buf.chunks_exact_mut(4);
+        let samples = self
+            .color_buf
+            .iter()
+            .zip(&self.alpha_buf)
+            .zip(&self.weight_buf);
+        for (p, ((c, a), w)) in pixels.zip(samples) {
+            Self::write_pixel(
+                p,
+                accum_color(*c),
+                accum_to_f64(*a),
+                accum_to_f64(*w),
+                self.gamma,
+            );
+        }
+    }
+
+    fn copy_pixels(&self, scene: &Scene, pixel_buf: &mut [u8]) {
+        // Copies the rendered pixels out for display, with the overlay drawn over the copy so it
+        // never reaches the samples.
+        pixel_buf.copy_from_slice(&self.pixel_buf);
+        if self.bbox_overlay {
+            for object in scene.iter() {
+                self.draw_bbox(pixel_buf, object.bounding_box());
+            }
+        }
+    }
+
+    fn draw_bbox(&self, pixel_buf: &mut [u8], bbox: Aabb) {
+        // Each of the 8 corners picks min or max on each axis by one bit of its index, and the
+        // 12 edges join the corners that differ by a single bit.
+        let (min, max) = (bbox.min(), bbox.max());
+        let corner = |i: usize| {
+            Vec3::new(
+                if i & 1 == 0 { min.x() } else { max.x() },
+                if i & 2 == 0 { min.y() } else { max.y() },
+                if i & 4 == 0 { min.z() } else { max.z() },
+            )
+        };
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.draw_line(pixel_buf, corner(i), corner(i | bit));
+                }
+            }
+        }
+    }
+
+    fn draw_line(&self, pixel_buf: &mut [u8], a: Vec3, b: Vec3) {
+        // Cut off any part of the line behind the camera, where it can't be projected. Depth is
+        // measured along the view direction, with the viewport at a depth of one.
+        let n = self.pixel_delta_u.cross(self.pixel_delta_v);
+        let depth = |p: Vec3| n.dot(p - self.center) / n.dot(self.pixel00_loc - self.center);
+        let near = 1.0e-3;
+        let (depth_a, depth_b) = (depth(a), depth(b));
+        if depth_a < near && depth_b < near {
+            return;
+        }
+        let clip_near = |p: Vec3, depth_p: f64, q: Vec3, depth_q: f64| {
+            if depth_p < near {
+                p + (near - depth_p) / (depth_q - depth_p) * (q - p)
+            } else {
+                p
+            }
+        };
+        let (a, b) = (
+            clip_near(a, depth_a, b, depth_b),
+            clip_near(b, depth_b, a, depth_a),
+        );
+
+        let to_pixel = |p| {
+            project(
+                self.center,
+                self.pixel00_loc,
+                self.pixel_delta_u,
+                self.pixel_delta_v,
+                p,
+            )
+        };
+        let (Some((x0, y0)), Some((x1, y1))) = (to_pixel(a), to_pixel(b)) else {
+            return;
+        };
+
+        // Clip the projected line to the view's pixels (Liang-Barsky), then step along it one
+        // pixel at a time in whichever direction it's longer.
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let (mut t0, mut t1) = (0.0_f64, 1.0_f64);
+        for (p, q) in [
+            (-dx, x0 + 0.5),
+            (dx, self.width as f64 - 0.5 - x0),
+            (-dy, y0 + 0.5),
+            (dy, self.height as f64 - 0.5 - y0),
+        ] {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return;
+                }
+            } else if p < 0.0 {
+                t0 = t0.max(q / p);
+            } else {
+                t1 = t1.min(q / p);
+            }
+        }
+        if t0 > t1 {
+            return;
+        }
+
+        let steps = ((t1 - t0) * dx.abs().max(dy.abs())).ceil().max(1.0) as usize;
+        for step in 0..=steps {
+            let t = t0 + (t1 - t0) * step as f64 / steps as f64;
+            let x = (x0 + t * dx).round();
+            let y = (y0 + t * dy).round();
+            if x >= 0.0 && x < self.width as f64 && y >= 0.0 && y < self.height as f64 {
+                let i = 4 * (y as usize * self.width + x as usize);
+                pixel_buf[i..i + 4].copy_from_slice(&BBOX_OVERLAY_COLOR);
+            }
+        }
+    }
+
+    fn restart(&mut self) {
+        // Discards all accumulated samples, to render again from the first pass.
+        self.color_buf.fill([0.0; 3]);
+        self.alpha_buf.fill(0.0);
+        self.weight_buf.fill(0.0);
+        self.square_buf.fill(0.0);
+        self.pixel_buf.fill(0);
+        self.start_row = self.region.1;
+        self.render_passes = 0;
+        self.pixels_rendered.store(0, Ordering::Relaxed);
+    }
+
+    fn set_region(&mut self, region: (usize, usize, usize, usize)) {
+        // Discards the samples within `region`, to render only that again from the first pass.
+        let (x0, y0, x1, y1) = region;
+        for y in y0..y1 {
+            let row = y * self.width;
+            self.color_buf[row + x0..row + x1].fill([0.0; 3]);
+            self.alpha_buf[row + x0..row + x1].fill(0.0);
+            self.weight_buf[row + x0..row + x1].fill(0.0);
+            self.square_buf[row + x0..row + x1].fill(0.0);
+            self.pixel_buf[4 * (row + x0)..4 * (row + x1)].fill(0);
+        }
+        self.region = region;
+        self.start_row = y0;
+        self.render_passes = 0;
+        self.pixels_rendered.store(0, Ordering::Relaxed);
+    }
+
+    pub fn render(&mut self, scene: &Scene, passes_wanted: usize) {
+        if self.render_passes >= passes_wanted {
+            return;
+        }
+
+        // With no pixels to render, every pass is already done.
+        let (region_x0, region_y0, region_x1, region_y1) = self.region;
+        if region_x0 >= region_x1 || region_y0 >= region_y1 {
+            self.render_passes = passes_wanted;
+            return;
+        }
+
+        let mut color_buf = vec![];
+        let mut alpha_buf = vec![];
+        let mut weight_buf = vec![];
+        let mut square_buf = vec![];
+
+        let mut pixel_buf = vec![];
+
+        std::mem::swap(&mut color_buf, &mut self.color_buf);
+        std::mem::swap(&mut alpha_buf, &mut self.alpha_buf);
+        std::mem::swap(&mut weight_buf, &mut self.weight_buf);
+        std::mem::swap(&mut square_buf, &mut self.square_buf);
+        std::mem::swap(&mut pixel_buf, &mut self.pixel_buf);
+
+        // Rows are rendered in bands, in chunks of 4 pixels across; bands are one row for scanline
+        // order. Every pixel's samples only depend on its position and pass, so the order chunks
+        // are rendered in doesn't change the image.
+        let band_rows = match self.pixel_order {
+            PixelOrder::Scanline => 1,
+            PixelOrder::Morton => MORTON_BAND_ROWS,
+        };
+
+        loop {
+            let band_y0 = self.start_row;
+            let band_y1 = usize::min(band_y0 + band_rows, region_y1);
+            let mut chunks: Vec<(usize, usize)> = (band_y0..band_y1)
+                .flat_map(|y| (region_x0..region_x1).step_by(4).map(move |x0| (x0, y)))
+                .collect();
+            if let PixelOrder::Morton = self.pixel_order {
+                chunks.sort_by_key(|&(x0, y)| morton_code((x0 - region_x0) / 4, y - band_y0));
+            }
+
+            for (x0, y) in chunks {
+                let i = y * self.width + x0;
+                let n = usize::min(4, region_x1 - x0);
+                let cs = &mut color_buf[i..i + n];
+                let als = &mut alpha_buf[i..i + n];
+                let ws = &mut weight_buf[i..i + n];
+                let sqs = &mut square_buf[i..i + n];
+                let ps = &mut pixel_buf[4 * i..4 * (i + n)];
+
+                // Seed each pixel's samples from its position in the whole image and the pass
+                // number, so they don't depend on which view the pixel happens to be in.
+                let mut rngs: [Rng; 4] = std::array::from_fn(|i| {
+                    let pixel_index = (y * self.image_width + self.view_x + x0 + i) as u64;
+                    let pass_index = (self.render_passes as u64) << 32;
+                    Rng::new(self.rng_seed.wrapping_add(pass_index | pixel_index))
+                });
+
+                for _ in 0..self.samples_per_pass {
+                    let samples = self.sample_colors(&mut rngs, scene, x0, y, n);
+                    let sums = cs.iter_mut().zip(als.iter_mut()).zip(ws.iter_mut());
+                    let sums = sums.zip(sqs.iter_mut());
+                    for ((((c, a), w), sq), (color, alpha, weight)) in sums.zip(samples) {
+                        let luminance = color.luminance();
+                        *sq += (weight * luminance * luminance) as Accum;
+                        let color = weight * color;
+                        c[0] += color.r() as Accum;
+                        c[1] += color.g() as Accum;
+                        c[2] += color.b() as Accum;
+                        *a += (weight * alpha) as Accum;
+                        *w += weight as Accum;
+                    }
+                }
+
+                let sums = cs.iter().zip(als.iter()).zip(ws.iter());
+                for (((c, a), w), p) in sums.zip(ps.chunks_exact_mut(4)) {
+                    Self::write_pixel(
+                        p,
+                        accum_color(*c),
+                        accum_to_f64(*a),
+                        accum_to_f64(*w),
+                        self.gamma,
+                    );
+                }
+            }
+
+            self.start_row = band_y1;
+            let pass_done = self.start_row >= region_y1;
+            if pass_done {
+                self.render_passes += 1;
+                self.start_row = region_y0;
+            }
+            self.pixels_rendered.fetch_add(
+                (band_y1 - band_y0) * (region_x1 - region_x0),
+                Ordering::Relaxed,
+            );
+            if pass_done || self.pause.load(Ordering::Acquire) {
+                break;
+            }
+        }
+
+        std::mem::swap(&mut color_buf, &mut self.color_buf);
+        std::mem::swap(&mut alpha_buf, &mut self.alpha_buf);
+        std::mem::swap(&mut weight_buf, &mut self.weight_buf);
+        std::mem::swap(&mut square_buf, &mut self.square_buf);
+        std::mem::swap(&mut pixel_buf, &mut self.pixel_buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quad::*;
+    use crate::sphere::*;
+
+    fn test_scene() -> Arc<Scene> {
+        // Diffuse ground under a metal, a glass and a diffuse sphere, lit by the sky.
+        let mut scene = Scene::new();
+        let sphere = |x: f64, y: f64, radius: f64, mat: Material| {
+            Sphere::new(Vec3::new(x, y, -1.0), radius, Arc::new(mat))
+        };
+        scene.add(sphere(
+            0.0,
+            -100.5,
+            100.0,
+            Material::lambertian(Color::new(0.8, 0.8, 0.0)),
+        ));
+        scene.add(sphere(
+            -1.0,
+            0.0,
+            0.5,
+            Material::metal(Color::new(0.8, 0.8, 0.8), 0.3),
+        ));
+        scene.add(sphere(
+            0.0,
+            0.0,
+            0.5,
+            Material::lambertian(Color::new(0.1, 0.2, 0.5)),
+        ));
+        scene.add(sphere(1.0, 0.0, 0.5, Material::dielectric(1.5)));
+        Arc::new(scene)
+    }
+
+    fn test_options() -> CameraOptions {
+        CameraOptions {
+            aspect_ratio: 2.0,
+            image_width: 32,
+            max_depth: 8,
+            ..Default::default()
+        }
+    }
+
+    fn render(num_views: u8, options: CameraOptions, passes: usize) -> Vec<u8> {
+        let mut camera = Camera::new(&test_scene(), 7, num_views, options);
+        camera.render_to_completion(passes);
+        camera.frame_rgba8()
+    }
+
+    #[test]
+    fn checkpoint_resumes_the_same_render() {
+        // Ten passes, a checkpoint, then ten more by another camera, are twenty passes.
+        let path = std::env::temp_dir().join(format!("raytracing-{}.chkpt", std::process::id()));
+        let mut camera = Camera::new(&test_scene(), 7, 2, test_options());
+        camera.render_to_completion(10);
+        camera.save_checkpoint(&path).expect("save_checkpoint");
+
+        let mut resumed = Camera::new(&test_scene(), 7, 2, test_options());
+        let loaded = resumed.load_checkpoint(&path);
+        std::fs::remove_file(&path).expect("remove_file");
+        loaded.expect("load_checkpoint");
+        resumed.render_to_completion(20);
+
+        assert!(resumed.frame_rgba8() == render(2, test_options(), 20));
+    }
+
+    #[test]
+    fn view_count_does_not_change_the_image() {
+        // Samples are seeded by pixel, not by view, so splitting the image differently is the
+        // same render.
+        assert!(render(1, test_options(), 4) == render(4, test_options(), 4));
+    }
+
+    #[test]
+    fn morton_order_does_not_change_the_image() {
+        // Nor does the order pixels are rendered in, even in a region that doesn't line up with
+        // chunks or bands.
+        let morton = || CameraOptions {
+            pixel_order: PixelOrder::Morton,
+            ..test_options()
+        };
+        assert!(render(3, morton(), 4) == render(3, test_options(), 4));
+
+        let render_region = |options: CameraOptions| {
+            let mut camera = Camera::new(&test_scene(), 7, 3, options);
+            camera.set_region(5, 3, 27, 13);
+            camera.render_to_completion(4);
+            camera.frame_rgba8()
+        };
+        assert!(render_region(morton()) == render_region(test_options()));
+    }
+
+    #[test]
+    fn new_options_and_scene_match_a_new_camera() {
+        // Reusing a camera for another render, as `animate` does, starts it over from scratch.
+        let mut scene = Scene::new();
+        let mat = Arc::new(Material::lambertian(Color::new(0.5, 0.5, 0.5)));
+        scene.add(Sphere::new(Vec3::new(0.5, 0.0, -1.5), 0.7, mat));
+        let scene = Arc::new(scene);
+        let options = || CameraOptions {
+            image_width: 24,
+            lookfrom: Vec3::new(0.0, 0.5, 0.5),
+            ..test_options()
+        };
+
+        let mut camera = Camera::new(&test_scene(), 7, 2, test_options());
+        camera.render_to_completion(4);
+        camera.set_scene(&scene);
+        camera.set_options(options());
+        camera.render_to_completion(4);
+
+        let mut new_camera = Camera::new(&scene, 7, 2, options());
+        new_camera.render_to_completion(4);
+        assert!(camera.frame_rgba8() == new_camera.frame_rgba8());
+    }
+
+    #[test]
+    fn single_threaded_matches_view_threads() {
+        let mut camera = Camera::new(&test_scene(), 7, 4, test_options());
+        camera.render_single_threaded(4);
+        assert!(camera.frame_rgba8() == render(4, test_options(), 4));
+    }
+
+    #[test]
+    fn stereo_eyes_differ_and_mono_is_unchanged() {
+        // Each half of a stereo frame is its own eye's image.
+        let stereo = |eye_separation: f64| {
+            let frame = render(
+                2,
+                CameraOptions {
+                    stereo: Some(StereoOptions { eye_separation }),
+                    ..test_options()
+                },
+                4,
+            );
+            let row_len = 4 * test_options().image_width as usize;
+            let (mut left, mut right) = (vec![], vec![]);
+            for row in frame.chunks_exact(2 * row_len) {
+                left.extend_from_slice(&row[..row_len]);
+                right.extend_from_slice(&row[row_len..]);
+            }
+            (left, right)
+        };
+
+        // The eyes are apart, so they see the scene differently.
+        let (left, right) = stereo(0.2);
+        assert!(left != right);
+
+        // The left eye is seeded like a mono render, so with no separation it's the same image,
+        // and mono renders are just as they were before stereo.
+        let (left, _) = stereo(0.0);
+        assert!(left == render(2, test_options(), 4));
+    }
+
+    #[test]
+    fn post_effects_apply_to_frames() {
+        // Re-encoding the linear image changes nothing by itself, so effects that don't do
+        // anything leave frames as they were.
+        let with_effects = |post_effects: Vec<PostEffect>| {
+            let options = CameraOptions {
+                post_effects,
+                ..test_options()
+            };
+            render(2, options, 4)
+        };
+        let plain = render(2, test_options(), 4);
+        let bloom = |threshold: f64| PostEffect::Bloom {
+            threshold,
+            radius: 3.0,
+        };
+        assert!(with_effects(vec![bloom(f64::INFINITY)]) == plain);
+        assert!(with_effects(vec![bloom(0.5)]) != plain);
+
+        let vignette = |strength: f64| PostEffect::Vignette { strength };
+        assert!(with_effects(vec![vignette(0.0)]) == plain);
+        assert!(with_effects(vec![vignette(0.5)]) != plain);
+
+        let aberration = |amount: f64| PostEffect::ChromaticAberration { amount };
+        assert!(with_effects(vec![aberration(0.0)]) == plain);
+        assert!(with_effects(vec![aberration(0.1)]) != plain);
+    }
+
+    #[test]
+    fn mis_is_no_noisier_than_either_strategy() {
+        // Direct light on a diffuse floor from a big dim light, which scattered rays find easily,
+        // and a small bright one, which only sampling it directly finds. Weighting both
+        // strategies should be no noisier than either on its own, and agree with them on average.
+        let albedo = Color::new(0.5, 0.5, 0.5);
+        let mut scene = Scene::new();
+        scene.add(Sphere::new(
+            Vec3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            Arc::new(Material::lambertian(albedo)),
+        ));
+        let light = |center: Vec3, radius: f64, emit: f64| {
+            let mat = Material::diffuse_light(Color::new(emit, emit, emit));
+            Sphere::new(center, radius, Arc::new(mat))
+        };
+        scene.add_light(light(Vec3::new(-2.0, 0.5, 0.0), 1.8, 1.0));
+        scene.add_light(light(Vec3::new(1.5, 1.5, 0.0), 0.05, 400.0));
+        let scene = Arc::new(scene);
+
+        let options = CameraOptions {
+            max_depth: 2,
+            sky_intensity: 0.0,
+            ..test_options()
+        };
+        let pause = Arc::new(AtomicBool::new(false));
+        let (_, _, views) = Camera::create_views(&options, &scene, 7, 1, &pause);
+        let view = &views[0];
+        let r = Ray::new(Vec3::new(0.0, 1.0, 1.0), Vec3::new(0.0, -1.0, -1.0));
+        let floor = || scene.hit(&r, view.ray_epsilon, f64::INFINITY).expect("hit");
+
+        let light_only = |rng: &mut Rng| {
+            let rec = floor();
+            let mut color = Color::new(0.0, 0.0, 0.0);
+            for light in scene.lights() {
+                let (dir, pdf) = light.sample_toward(rec.p, rng).expect("sample_toward");
+                let cos_theta = rec.normal.dot(dir);
+                let shadow_ray = Ray::new(rec.p, dir);
+                let light_rec = light.hit(&shadow_ray, view.ray_epsilon, f64::INFINITY);
+                if let Some(light_rec) = light_rec
+                    && cos_theta > 0.0
+                {
+                    let emitted = light_rec.mat.emitted(0.0, 0.0, light_rec.p, true);
+                    color += (cos_theta / (std::f64::consts::PI * pdf)) * emitted;
+                }
+            }
+            albedo * color
+        };
+        let brdf_only = |rng: &mut Rng| {
+            let rec = floor();
+            let sc_rec = rec.mat.scatter(rng, &r, &rec).expect("scatter");
+            let path = PathState::default();
+            sc_rec.attenuation * view.ray_color(rng, 1, &sc_rec.scattered, &scene, path)
+        };
+        let mis = |rng: &mut Rng| {
+            view.hit_color(rng, 2, &r, Some(floor()), &scene, PathState::default())
+                .0
+        };
+
+        let n = 20000;
+        let stats = |estimate: &dyn Fn(&mut Rng) -> Color| {
+            // Mean and variance of the luminance of `n` samples.
+            let mut rng = Rng::new(1);
+            let samples: Vec<f64> = (0..n).map(|_| estimate(&mut rng).luminance()).collect();
+            let mean = samples.iter().sum::<f64>() / n as f64;
+            let variance = samples.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / n as f64;
+            (mean, variance)
+        };
+        let (light_mean, light_variance) = stats(&light_only);
+        let (brdf_mean, brdf_variance) = stats(&brdf_only);
+        let (mis_mean, mis_variance) = stats(&mis);
+
+        assert!((mis_mean - light_mean).abs() < 0.02 * light_mean);
+        assert!((mis_mean - brdf_mean).abs() < 3.0 * (brdf_variance / n as f64).sqrt());
+        assert!(mis_variance <= light_variance);
+        assert!(mis_variance <= brdf_variance);
+    }
+
+    #[test]
+    fn dropped_cameras_join_their_view_threads() {
+        // Each view thread holds the scene through its view until it exits, so once a camera
+        // is dropped, even partway through a pass, nothing else should.
+        let scene = test_scene();
+        for passes in 0..20 {
+            let mut camera = Camera::new(&scene, 7, 4, test_options());
+            camera.render_to_completion(passes % 3);
+            camera.render(Instant::now() + Duration::from_micros(100));
+            drop(camera);
+            assert_eq!(Arc::strong_count(&scene), 1);
+        }
+    }
+
+    #[test]
+    fn frames_are_whole_images_of_rgba_bytes() {
+        // Four bytes for every pixel, which with a single view are just that view's pixels.
+        let mut camera = Camera::new(&test_scene(), 7, 1, test_options());
+        camera.render_to_completion(2);
+        let frame = camera.frame_rgba8();
+        assert_eq!((camera.get_width(), camera.get_height()), (32, 16));
+        assert_eq!(frame.len(), 4 * 32 * 16);
+
+        let mut view_pixels = vec![];
+        camera.for_each_view(|_, _, _, pixel_buf| view_pixels.push(pixel_buf.to_vec()));
+        assert!(view_pixels == [frame]);
+    }
+
+    #[test]
+    fn depth_is_darker_nearer_the_camera() {
+        // A sphere about two units away is darker than one about five units away, and both are
+        // darker than the white of a miss.
+        let mat = Arc::new(Material::lambertian(Color::new(0.5, 0.5, 0.5)));
+        let mut scene = Scene::new();
+        scene.add(Sphere::new(
+            Vec3::new(-1.0, 0.0, -2.0),
+            0.5,
+            Arc::clone(&mat),
+        ));
+        scene.add(Sphere::new(Vec3::new(2.5, 0.0, -5.0), 1.0, mat));
+        let options = CameraOptions {
+            render_mode: RenderMode::Depth {
+                near: 1.0,
+                far: 10.0,
+            },
+            ..test_options()
+        };
+        let mut camera = Camera::new(&Arc::new(scene), 7, 1, options);
+        camera.render_to_completion(1);
+
+        let frame = camera.frame_linear();
+        let gray_at = |p: Vec3| {
+            let (x, y) = camera.project(p).expect("project");
+            frame[y.round() as usize * camera.get_width() + x.round() as usize].r()
+        };
+        let expected = |p: Vec3| (p.length() - 1.0) / 9.0;
+        let nearest_point = |center: Vec3, radius: f64| center - radius * center.unit();
+        let near = nearest_point(Vec3::new(-1.0, 0.0, -2.0), 0.5);
+        let far = nearest_point(Vec3::new(2.5, 0.0, -5.0), 1.0);
+        assert!((gray_at(near) - expected(near)).abs() < 0.02);
+        assert!((gray_at(far) - expected(far)).abs() < 0.02);
+        assert!(gray_at(near) < gray_at(far));
+        assert_eq!(frame[0].r(), 1.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn depth_needs_far_beyond_near() {
+        let options = CameraOptions {
+            render_mode: RenderMode::Depth {
+                near: 5.0,
+                far: 5.0,
+            },
+            ..test_options()
+        };
+        Camera::new(&test_scene(), 7, 1, options);
+    }
+
+    #[test]
+    fn render_steps_match_a_blocking_render() {
+        // Polling until complete renders each view exactly `max_passes` passes, the same image
+        // as rendering them all at once.
+        let options = || CameraOptions {
+            max_passes: Some(5),
+            ..test_options()
+        };
+        let mut camera = Camera::new(&test_scene(), 7, 3, options());
+        while camera.try_render_step() == RenderStatus::InProgress {
+            std::thread::sleep(Duration::from_micros(100));
+        }
+        let mut blocking = Camera::new(&test_scene(), 7, 3, options());
+        blocking.render_to_completion(5);
+
+        let passes = |camera: &Camera| -> Vec<usize> {
+            let view_passes =
+                |view: &Arc<Mutex<View>>| view.lock().expect("view mutex").render_passes;
+            camera.views.iter().map(view_passes).collect()
+        };
+        assert_eq!(passes(&camera), [5, 5, 5]);
+        assert_eq!(passes(&camera), passes(&blocking));
+        assert!(camera.frame_rgba8() == blocking.frame_rgba8());
+        assert_eq!(camera.try_render_step(), RenderStatus::Completed);
+    }
+
+    #[test]
+    fn estimated_variance_falls_with_passes() {
+        // Infinite until pixels have two samples, then falling about as one over their count.
+        let mut camera = Camera::new(&test_scene(), 7, 2, test_options());
+        camera.render_to_completion(1);
+        assert_eq!(camera.estimated_variance(), f64::INFINITY);
+
+        let mut last = f64::INFINITY;
+        for passes in [2, 8, 32] {
+            camera.render_to_completion(passes);
+            let variance = camera.estimated_variance();
+            assert!(variance < 0.5 * last);
+            last = variance;
+        }
+    }
+
+    #[test]
+    fn regions_leave_the_rest_of_the_image_alone() {
+        // Rendering more passes in a region across views changes pixels inside it, and only
+        // those.
+        let mut camera = Camera::new(&test_scene(), 7, 3, test_options());
+        camera.render_to_completion(2);
+        let before = camera.frame_rgba8();
+        camera.set_region(5, 3, 27, 13);
+        camera.render_to_completion(16);
+        let after = camera.frame_rgba8();
+
+        let mut changed_inside = 0;
+        for (i, (a, b)) in before
+            .chunks_exact(4)
+            .zip(after.chunks_exact(4))
+            .enumerate()
+        {
+            let (x, y) = (i % 32, i / 32);
+            if (5..27).contains(&x) && (3..13).contains(&y) {
+                changed_inside += (a != b) as usize;
+            } else {
+                assert_eq!(a, b);
+            }
+        }
+        assert!(changed_inside > 22 * 10 / 2);
+    }
+
+    #[test]
+    fn project_maps_viewport_corners_to_pixel_corners() {
+        // The 90 degree view looks one unit ahead at a viewport two units high and four wide,
+        // with 32 by 16 pixels centered on whole numbers, so its corners are half a pixel out.
+        let camera = Camera::new(&test_scene(), 7, 2, test_options());
+        let corners = [
+            (Vec3::new(-2.0, 1.0, -1.0), (-0.5, -0.5)),
+            (Vec3::new(2.0, 1.0, -1.0), (31.5, -0.5)),
+            (Vec3::new(-2.0, -1.0, -1.0), (-0.5, 15.5)),
+            (Vec3::new(2.0, -1.0, -1.0), (31.5, 15.5)),
+        ];
+        for (p, (x, y)) in corners {
+            for distance in [1.0, 3.0] {
+                let (px, py) = camera.project(distance * p).expect("project");
+                assert!((px - x).abs() < 1e-9 && (py - y).abs() < 1e-9);
+            }
+        }
+
+        // Pixel rays project back onto their own pixels, and points behind the camera nowhere.
+        let (px, py) = camera
+            .project(camera.pixel_ray(7, 12).at(3.0))
+            .expect("project");
+        assert!((px - 7.0).abs() < 1e-9 && (py - 12.0).abs() < 1e-9);
+        assert!(camera.project(Vec3::new(0.0, 0.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn edge_mask_outlines_a_sphere() {
+        // With any normals allowed, edges are exactly the pixels whose center rays hit the
+        // sphere beside ones that miss it, or the other way around, even across views.
+        let mat = Arc::new(Material::lambertian(Color::new(0.5, 0.5, 0.5)));
+        let mut scene = Scene::new();
+        scene.add(Sphere::new(Vec3::new(0.3, 0.1, -3.0), 1.0, mat));
+        let scene = Arc::new(scene);
+        let camera = Camera::new(&scene, 7, 3, test_options());
+        let (w, h) = (camera.get_width(), camera.get_height());
+        let hits = |x: usize, y: usize| {
+            let r = camera.pixel_ray(x, y);
+            scene
+                .hit(&r, camera.options.ray_epsilon, f64::INFINITY)
+                .is_some()
+        };
+
+        let mask = camera.edge_mask(-2.0);
+        let mut edges = 0;
+        for y in 0..h {
+            for x in 0..w {
+                let neighbors = [
+                    (x.wrapping_sub(1), y),
+                    (x + 1, y),
+                    (x, y.wrapping_sub(1)),
+                    (x, y + 1),
+                ];
+                let expected = neighbors
+                    .into_iter()
+                    .filter(|&(nx, ny)| nx < w && ny < h)
+                    .any(|(nx, ny)| hits(nx, ny) != hits(x, y));
+                assert_eq!(mask[y * w + x], expected);
+                edges += expected as usize;
+            }
+        }
+        assert!(edges > 20 && hits(17, 8) && !mask[8 * w + 17]);
+
+        // Asking for flatter surfaces marks where the sphere curves away inside it too.
+        let curved = camera.edge_mask(0.9999);
+        assert!(
+            curved
+                .iter()
+                .zip(&mask)
+                .all(|(&curved, &edge)| curved || !edge)
+        );
+        assert!(curved.iter().filter(|&&edge| edge).count() > edges);
+    }
+
+    #[test]
+    fn pixels_pick_the_objects_under_them() {
+        // Each sphere of the test scene is picked at the pixel its front projects to, by its
+        // position among the objects; the sky above them picks nothing.
+        let scene = test_scene();
+        let camera = Camera::new(&scene, 7, 2, test_options());
+        let pick = |p: Vec3| {
+            let (x, y) = camera.project(p).expect("project");
+            let r = camera.pixel_ray(x.round() as usize, y.round() as usize);
+            scene.pick(&r, camera.options.ray_epsilon)
+        };
+        assert_eq!(pick(Vec3::new(-1.0, 0.0, -0.5)), Some(1));
+        assert_eq!(pick(Vec3::new(0.0, 0.0, -0.5)), Some(2));
+        assert_eq!(pick(Vec3::new(1.0, 0.0, -0.5)), Some(3));
+        assert_eq!(pick(Vec3::new(-0.5, -0.5, -0.6)), Some(0));
+        assert_eq!(pick(Vec3::new(0.0, 2.0, -1.0)), None);
+    }
+
+    #[test]
+    fn unshadowed_shadow_catchers_are_transparent() {
+        // A sphere over a shadow catcher floor, lit from above: the floor is see-through except
+        // in the sphere's shadow, where it's opaque.
+        let mut scene = Scene::new();
+        scene.add(Quad::new(
+            Vec3::new(-10.0, -0.8, 0.0),
+            Vec3::new(20.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -20.0),
+            Arc::new(Material::shadow_catcher()),
+        ));
+        let clay = Material::lambertian(Color::new(0.5, 0.5, 0.5));
+        scene.add(Sphere::new(Vec3::new(0.5, 0.0, -4.0), 0.5, Arc::new(clay)));
+        scene.add_light(PointLight {
+            pos: Vec3::new(0.0, 3.0, -4.0),
+            color: Color::new(10.0, 10.0, 10.0),
+        });
+        let options = CameraOptions {
+            vfov: 30.0,
+            alpha_background: true,
+            ..test_options()
+        };
+        let mut camera = Camera::new(&Arc::new(scene), 7, 2, options);
+        camera.render_to_completion(1);
+
+        let frame = camera.frame_rgba8();
+        let alpha_at = |p: Vec3| {
+            let (x, y) = camera.project(p).expect("project");
+            frame[4 * (y.round() as usize * camera.get_width() + x.round() as usize) + 3]
+        };
+        assert_eq!(alpha_at(Vec3::new(-1.5, -0.8, -4.0)), 0);
+        assert_eq!(alpha_at(Vec3::new(0.633, -0.8, -4.0)), 255);
+        assert_eq!(alpha_at(Vec3::new(0.5, 0.0, -3.5)), 255);
+    }
+
+    #[test]
+    fn alpha_background_makes_misses_transparent() {
+        // With alpha_background an empty scene is transparent black everywhere, while the test
+        // scene keeps its spheres opaque and only the sky above them clear.
+        let empty = Arc::new(Scene::new());
+        let options = CameraOptions {
+            alpha_background: true,
+            ..test_options()
+        };
+        let mut camera = Camera::new(&empty, 7, 2, options);
+        camera.render_to_completion(1);
+        assert!(camera.frame_rgba8().iter().all(|&b| b == 0));
+
+        let alpha_at = |camera: &Camera, p: Vec3| {
+            let (x, y) = camera.project(p).expect("project");
+            camera.frame_rgba8()[4 * (y as usize * camera.get_width() + x as usize) + 3]
+        };
+        let options = CameraOptions {
+            alpha_background: true,
+            ..test_options()
+        };
+        let mut camera = Camera::new(&test_scene(), 7, 2, options);
+        camera.render_to_completion(1);
+        assert_eq!(alpha_at(&camera, Vec3::new(0.0, 0.0, -0.5)), 255);
+        assert_eq!(alpha_at(&camera, Vec3::new(0.0, 0.9, -1.0)), 0);
+
+        let mut camera = Camera::new(&test_scene(), 7, 2, test_options());
+        camera.render_to_completion(1);
+        assert_eq!(alpha_at(&camera, Vec3::new(0.0, 0.9, -1.0)), 255);
+    }
+
+    #[test]
+    fn render_for_stops_within_its_budget() {
+        // An unlimited render gives up soon after its time is spent, having finished some
+        // passes, while a limited one stops early once every pass is done.
+        let mut camera = Camera::new(&test_scene(), 7, 2, test_options());
+        let start = Instant::now();
+        let passes = camera.render_for(Duration::from_millis(100));
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(passes >= 1);
+
+        let options = CameraOptions {
+            max_passes: Some(3),
+            ..test_options()
+        };
+        let mut camera = Camera::new(&test_scene(), 7, 2, options);
+        let start = Instant::now();
+        assert_eq!(camera.render_for(Duration::from_secs(60)), 3);
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(camera.is_complete());
+    }
+
+    #[test]
+    fn pooled_cameras_match_their_own_renders() {
+        // A main view and a top-down one of the same scene, polled in turn on two shared
+        // threads, each come out as they would with threads of their own.
+        let main = || CameraOptions {
+            max_passes: Some(3),
+            ..test_options()
+        };
+        let top = || CameraOptions {
+            lookfrom: Vec3::new(0.0, 4.0, -1.0),
+            lookat: Vec3::new(0.0, 0.0, -1.0),
+            vup: Vec3::new(0.0, 0.0, -1.0),
+            max_passes: Some(3),
+            ..test_options()
+        };
+        let scene = test_scene();
+        let pool = Arc::new(RenderPool::new(2));
+        let mut cameras = [
+            Camera::with_pool(&scene, 7, 2, main(), &pool),
+            Camera::with_pool(&scene, 9, 3, top(), &pool),
+        ];
+        loop {
+            let statuses = cameras.each_mut().map(|camera| camera.try_render_step());
+            if statuses
+                .iter()
+                .all(|&status| status == RenderStatus::Completed)
+            {
+                break;
+            }
+            std::thread::sleep(Duration::from_micros(100));
+        }
+
+        let mut own_main = Camera::new(&scene, 7, 2, main());
+        own_main.render_to_completion(3);
+        let mut own_top = Camera::new(&scene, 9, 3, top());
+        own_top.render_to_completion(3);
+        assert!(cameras[0].frame_rgba8() == own_main.frame_rgba8());
+        assert!(cameras[1].frame_rgba8() == own_top.frame_rgba8());
+        assert!(cameras[0].frame_rgba8() != cameras[1].frame_rgba8());
+    }
+
+    #[test]
+    fn procedural_backgrounds_color_the_misses() {
+        // Every camera ray misses an empty scene, so the closure colors the whole image: red
+        // below the horizon and blue above it.
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&calls);
+        let options = CameraOptions {
+            background: Background::procedural(move |dir| {
+                counted.fetch_add(1, Ordering::Relaxed);
+                if dir.y() < 0.0 {
+                    Color::new(1.0, 0.0, 0.0)
+                } else {
+                    Color::new(0.0, 0.0, 1.0)
+                }
+            }),
+            ..test_options()
+        };
+        let mut camera = Camera::new(&Arc::new(Scene::new()), 7, 2, options);
+        camera.render_to_completion(1);
+        assert!(calls.load(Ordering::Relaxed) >= camera.get_width() * camera.get_height());
+
+        let frame = camera.frame_rgba8();
+        let bottom = 4 * (camera.get_height() - 1) * camera.get_width();
+        assert_eq!(frame[0..4], [0, 0, 255, 255]);
+        assert_eq!(frame[bottom..bottom + 4], [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn ambient_occlusion_darkens_crevices() {
+        // Where a floor meets a wall, half the hemisphere is blocked, so the corner comes out
+        // darker than open floor further from the wall than the occlusion radius.
+        let mut scene = Scene::new();
+        let clay = || Arc::new(Material::lambertian(Color::new(0.5, 0.5, 0.5)));
+        scene.add(Quad::new(
+            Vec3::new(-3.0, -0.5, 0.0),
+            Vec3::new(0.0, 0.0, -6.0),
+            Vec3::new(3.5, 0.0, 0.0),
+            clay(),
+        ));
+        scene.add(Quad::new(
+            Vec3::new(0.5, -0.5, 0.0),
+            Vec3::new(0.0, 0.0, -6.0),
+            Vec3::new(0.0, 3.0, 0.0),
+            clay(),
+        ));
+        let options = CameraOptions {
+            render_mode: RenderMode::AmbientOcclusion {
+                radius: 1.0,
+                samples: 64,
+            },
+            ..test_options()
+        };
+        let mut camera = Camera::new(&Arc::new(scene), 7, 2, options);
+        camera.render_to_completion(4);
+
+        let frame = camera.frame_rgba8();
+        let gray_at = |p: Vec3| {
+            let (x, y) = camera.project(p).expect("project");
+            frame[4 * (y as usize * camera.get_width() + x as usize)]
+        };
+        let corner = gray_at(Vec3::new(0.4, -0.5, -2.0));
+        let open = gray_at(Vec3::new(-1.5, -0.5, -2.0));
+        assert!(open > 240);
+        assert!(corner < open - 40);
     }
 }
@@ -4,6 +4,7 @@ use crate::vec3::*;
 pub struct Color(Vec3);
 
 impl Color {
+    #[inline]
     pub fn new(r: f64, g: f64, b: f64) -> Self {
         Self(Vec3::new(r, g, b))
     }
@@ -12,30 +13,93 @@ impl Color {
         Self(v3)
     }
 
+    pub fn from_srgb8(r: u8, g: u8, b: u8) -> Self {
+        // Decodes 8-bit sRGB, as most images are stored, to the linear values lighting needs.
+        Self::new(
+            srgb_to_linear(r as f64 / 255.0),
+            srgb_to_linear(g as f64 / 255.0),
+            srgb_to_linear(b as f64 / 255.0),
+        )
+    }
+
+    #[inline]
     pub fn r(&self) -> f64 {
         self.0.x()
     }
 
+    #[inline]
     pub fn g(&self) -> f64 {
         self.0.y()
     }
 
+    #[inline]
     pub fn b(&self) -> f64 {
         self.0.z()
     }
+
+    pub fn luminance(&self) -> f64 {
+        // Rec. 709 weights for linear RGB.
+        0.2126 * self.r() + 0.7152 * self.g() + 0.0722 * self.b()
+    }
+
+    pub fn to_grayscale(&self) -> Color {
+        let l = self.luminance();
+        Color::new(l, l, l)
+    }
+
+    pub fn clamp01(&self) -> Color {
+        Color::new(
+            self.r().clamp(0.0, 1.0),
+            self.g().clamp(0.0, 1.0),
+            self.b().clamp(0.0, 1.0),
+        )
+    }
+
+    pub fn saturate(&self) -> Color {
+        self.clamp01()
+    }
+}
+
+pub fn srgb_to_linear(x: f64) -> f64 {
+    // The sRGB transfer function is linear near black and a 2.4 power curve above it.
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
 }
 
 impl std::ops::Add for Color {
     type Output = Color;
 
+    #[inline]
     fn add(self, rhs: Color) -> Color {
         Color(self.0 + rhs.0)
     }
 }
 
+impl std::ops::Sub for Color {
+    type Output = Color;
+
+    #[inline]
+    fn sub(self, rhs: Color) -> Color {
+        Color(self.0 - rhs.0)
+    }
+}
+
+impl std::ops::Neg for Color {
+    type Output = Color;
+
+    #[inline]
+    fn neg(self) -> Color {
+        Color(-self.0)
+    }
+}
+
 impl std::ops::Mul for Color {
     type Output = Color;
 
+    #[inline]
     fn mul(self, rhs: Color) -> Color {
         Color(self.0 * rhs.0)
     }
@@ -44,13 +108,99 @@ impl std::ops::Mul for Color {
 impl std::ops::Mul<Color> for f64 {
     type Output = Color;
 
+    #[inline]
     fn mul(self, rhs: Color) -> Color {
         Color(self * rhs.0)
     }
 }
 
 impl std::ops::AddAssign for Color {
+    #[inline]
     fn add_assign(&mut self, rhs: Color) {
         self.0 += rhs.0;
     }
 }
+
+impl std::iter::Sum for Color {
+    fn sum<I: Iterator<Item = Color>>(iter: I) -> Color {
+        Color(iter.map(|c| c.0).sum())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(c: Color) -> [f64; 3] {
+        [c.r(), c.g(), c.b()]
+    }
+
+    #[test]
+    fn srgb_decodes_to_linear() {
+        // Mid-gray in sRGB is about a fifth of the light of white, and the ends stay put.
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 0.001);
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-12);
+
+        // The linear and power segments meet without a jump.
+        let below = srgb_to_linear(0.04045);
+        let above = srgb_to_linear(0.04045 + 1e-9);
+        assert!((above - below).abs() < 1e-6);
+
+        let c = Color::from_srgb8(0, 128, 255);
+        assert!(c.r() == 0.0 && (c.g() - 0.2158).abs() < 0.001 && (c.b() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn colors_multiply_per_channel() {
+        // Light filtered by a surface keeps each channel's fraction of it.
+        let product = Color::new(1.0, 0.5, 0.0) * Color::new(0.5, 1.0, 1.0);
+        assert_eq!(rgb(product), [0.5, 0.5, 0.0]);
+        assert_eq!(rgb(2.0 * Color::new(1.0, 0.5, 0.25)), [2.0, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn luminance_weights_green_most() {
+        // White has a luminance of one, shared out by the Rec. 709 weights, and grayscale keeps
+        // it in every channel.
+        assert!((Color::new(1.0, 1.0, 1.0).luminance() - 1.0).abs() < 1e-12);
+        assert_eq!(Color::new(1.0, 0.0, 0.0).luminance(), 0.2126);
+        assert_eq!(Color::new(0.0, 1.0, 0.0).luminance(), 0.7152);
+        assert_eq!(Color::new(0.0, 0.0, 1.0).luminance(), 0.0722);
+
+        let c = Color::new(0.8, 0.4, 0.2);
+        let gray = c.to_grayscale();
+        assert_eq!(rgb(gray), [c.luminance(); 3]);
+        assert!((gray.luminance() - c.luminance()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn clamp01_keeps_channels_in_range() {
+        // Each channel is clamped on its own, and `saturate` is the same thing.
+        let c = Color::new(-0.5, 0.25, 3.0);
+        assert_eq!(rgb(c.clamp01()), [0.0, 0.25, 1.0]);
+        assert_eq!(rgb(c.saturate()), rgb(c.clamp01()));
+        assert_eq!(rgb(Color::new(0.0, 1.0, 0.5).clamp01()), [0.0, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn colors_sum_per_channel() {
+        // Summing adds each channel, and nothing sums to black.
+        let colors = [
+            Color::new(0.5, 0.0, 1.0),
+            Color::new(0.25, 2.0, 0.0),
+            Color::new(0.25, 0.5, 1.0),
+        ];
+        assert_eq!(rgb(colors.iter().copied().sum()), [1.0, 2.5, 2.0]);
+        assert_eq!(rgb(std::iter::empty::<Color>().sum()), [0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn colors_subtract_and_negate_per_channel() {
+        // Differences can go negative, e.g. for the change between two images.
+        let (a, b) = (Color::new(1.0, 0.5, 0.25), Color::new(0.5, 1.0, 0.25));
+        assert_eq!(rgb(a - b), [0.5, -0.5, 0.0]);
+        assert_eq!(rgb(-a), [-1.0, -0.5, -0.25]);
+        assert_eq!(rgb(a - b), rgb(a + -b));
+    }
+}
@@ -0,0 +1,176 @@
+use crate::aabb::*;
+use crate::hit_record::*;
+use crate::material::*;
+use crate::ray::*;
+use crate::vec3::*;
+
+use std::sync::Arc;
+
+pub struct Cone {
+    apex: Vec3,
+    axis: Vec3, // Unit vector from the apex to the center of the base
+    height: f64,
+    radius: f64, // Radius of the base
+    capped: bool,
+    mat: Arc<Material>,
+}
+
+impl Cone {
+    pub fn new(apex: Vec3, base: Vec3, radius: f64, capped: bool, mat: Arc<Material>) -> Self {
+        let height = (base - apex).length();
+
+        Self {
+            apex,
+            axis: (base - apex) / height,
+            height,
+            radius,
+            capped,
+            mat,
+        }
+    }
+
+    fn outward_normal(&self, v: Vec3) -> Vec3 {
+        // Gradient of |v_perp|^2 - k^2 y^2, where v is relative to the apex, y is the distance
+        // along the axis and k is the slope of the side.
+        let k = self.radius / self.height;
+        let y = v.dot(self.axis);
+        let n = (v - y * self.axis) - k * k * y * self.axis;
+
+        // The gradient vanishes at the apex itself, so just point back along the axis there.
+        if n.near_zero() { -self.axis } else { n.unit() }
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        let base = self.apex + self.height * self.axis;
+        Aabb::new(self.apex, self.apex).union(Aabb::disc(base, self.axis, self.radius))
+    }
+
+    pub fn hit<'s>(&'s self, r: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord<'s>> {
+        let k = self.radius / self.height;
+        let m = 1.0 + k * k;
+
+        let oc = r.pos - self.apex;
+        let oc_axial = oc.dot(self.axis);
+        let dir_axial = r.dir.dot(self.axis);
+
+        // Solve a*t^2 + 2*h*t + c = 0 for |v|^2 = (1 + k^2) * y^2, with v = oc + t * dir.
+        let a = r.dir.length_squared() - m * dir_axial * dir_axial;
+        let h = r.dir.dot(oc) - m * dir_axial * oc_axial;
+        let c = oc.length_squared() - m * oc_axial * oc_axial;
+
+        let roots = if a.abs() < 1.0e-12 {
+            // The ray is parallel to the side of the cone, so there's at most one root.
+            if h.abs() < 1.0e-12 {
+                [f64::NAN, f64::NAN]
+            } else {
+                [-c / (2.0 * h), f64::NAN]
+            }
+        } else {
+            let discriminant = h * h - a * c;
+            if discriminant < 0.0 {
+                [f64::NAN, f64::NAN]
+            } else {
+                let sqrtd = discriminant.sqrt();
+                let (t0, t1) = ((-h - sqrtd) / a, (-h + sqrtd) / a);
+                [f64::min(t0, t1), f64::max(t0, t1)]
+            }
+        };
+
+        let mut closest: Option<(f64, Vec3)> = None;
+        let mut closest_so_far = ray_tmax;
+
+        // Side: the nearest root on the half of the double cone between the apex and the base.
+        for root in roots {
+            // NaN (no root) fails both comparisons, so check for it explicitly.
+            if root.is_nan() || root <= ray_tmin || root >= closest_so_far {
+                continue;
+            }
+            let y = oc_axial + root * dir_axial;
+            if (0.0..=self.height).contains(&y) {
+                closest = Some((root, self.outward_normal(oc + root * r.dir)));
+                closest_so_far = root;
+                break;
+            }
+        }
+
+        // Base cap: a disk at the far end of the axis.
+        if self.capped && dir_axial != 0.0 {
+            let root = (self.height - oc_axial) / dir_axial;
+            if root > ray_tmin && root < closest_so_far {
+                let v = oc + root * r.dir;
+                let v_perp = v - v.dot(self.axis) * self.axis;
+                if v_perp.length_squared() <= self.radius * self.radius {
+                    closest = Some((root, self.axis));
+                }
+            }
+        }
+
+        closest.map(|(root, outward_normal)| {
+            HitRecord::new(r, root, outward_normal, (0.0, 0.0), &self.mat)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::*;
+
+    fn cone(capped: bool) -> Cone {
+        // Apex at y = 2 over a base of radius one on the origin.
+        let mat = Arc::new(Material::lambertian(Color::new(0.5, 0.5, 0.5)));
+        Cone::new(
+            Vec3::new(0.0, 2.0, 0.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            1.0,
+            capped,
+            mat,
+        )
+    }
+
+    fn hit(cone: &Cone, pos: Vec3, dir: Vec3) -> Option<(f64, Vec3, bool)> {
+        let rec = cone.hit(&Ray::new(pos, dir), 0.001, f64::INFINITY)?;
+        Some((rec.t, rec.normal, rec.front_face))
+    }
+
+    #[test]
+    fn side_hits_face_out_and_up() {
+        // Halfway up, the side is half the base's radius out, and slopes at one in two.
+        let pos = Vec3::new(-5.0, 1.0, 0.0);
+        let (t, normal, front_face) =
+            hit(&cone(false), pos, Vec3::new(1.0, 0.0, 0.0)).expect("hit");
+        assert!((t - 4.5).abs() < 1e-12);
+        assert!((normal - Vec3::new(-2.0, 1.0, 0.0).unit()).near_zero());
+        assert!(front_face);
+
+        // The other half of the double cone, above the apex, isn't part of it.
+        let pos = Vec3::new(-5.0, 3.0, 0.0);
+        assert!(hit(&cone(true), pos, Vec3::new(1.0, 0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn apex_and_base() {
+        // Straight down the axis onto the apex, where the normal points back up it.
+        let pos = Vec3::new(0.0, 5.0, 0.0);
+        let (t, normal, front_face) =
+            hit(&cone(false), pos, Vec3::new(0.0, -1.0, 0.0)).expect("hit");
+        assert!((t - 3.0).abs() < 1e-9);
+        assert!((normal - Vec3::new(0.0, 1.0, 0.0)).near_zero());
+        assert!(front_face);
+
+        // Up onto the base, which is open without a cap, showing the inside of the side.
+        let pos = Vec3::new(0.5, -5.0, 0.0);
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let (t, normal, front_face) = hit(&cone(true), pos, up).expect("hit");
+        assert!((t - 5.0).abs() < 1e-12);
+        assert!((normal - Vec3::new(0.0, -1.0, 0.0)).near_zero());
+        assert!(front_face);
+        let (t, _, front_face) = hit(&cone(false), pos, up).expect("hit");
+        assert!((t - 6.0).abs() < 1e-12);
+        assert!(!front_face);
+
+        // Outside the base's radius, there's nothing either way.
+        let pos = Vec3::new(1.5, -5.0, 0.0);
+        assert!(hit(&cone(true), pos, up).is_none());
+    }
+}
@@ -0,0 +1,161 @@
+use crate::aabb::*;
+use crate::hit_record::*;
+use crate::material::*;
+use crate::ray::*;
+use crate::vec3::*;
+
+use std::sync::Arc;
+
+pub struct Cylinder {
+    base: Vec3, // Center of the bottom end
+    axis: Vec3, // Unit vector from the bottom end to the top end
+    height: f64,
+    radius: f64,
+    capped: bool,
+    mat: Arc<Material>,
+}
+
+impl Cylinder {
+    pub fn new(base: Vec3, top: Vec3, radius: f64, capped: bool, mat: Arc<Material>) -> Self {
+        let height = (top - base).length();
+
+        Self {
+            base,
+            axis: (top - base) / height,
+            height,
+            radius,
+            capped,
+            mat,
+        }
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        let top = self.base + self.height * self.axis;
+        Aabb::disc(self.base, self.axis, self.radius).union(Aabb::disc(top, self.axis, self.radius))
+    }
+
+    pub fn hit<'s>(&'s self, r: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord<'s>> {
+        // Split the ray into parts along and perpendicular to the cylinder axis.
+        let oc = r.pos - self.base;
+        let oc_axial = oc.dot(self.axis);
+        let oc_perp = oc - oc_axial * self.axis;
+        let dir_axial = r.dir.dot(self.axis);
+        let dir_perp = r.dir - dir_axial * self.axis;
+
+        let mut closest: Option<(f64, Vec3)> = None;
+        let mut closest_so_far = ray_tmax;
+
+        // Side: find the nearest root of |oc_perp + t * dir_perp| = radius within the segment.
+        let a = dir_perp.length_squared();
+        if a > 0.0 {
+            let h = dir_perp.dot(oc_perp);
+            let c = oc_perp.length_squared() - self.radius * self.radius;
+
+            let discriminant = h * h - a * c;
+            if discriminant >= 0.0 {
+                let sqrtd = discriminant.sqrt();
+
+                for root in [(-h - sqrtd) / a, (-h + sqrtd) / a] {
+                    if root <= ray_tmin || root >= closest_so_far {
+                        continue;
+                    }
+                    let y = oc_axial + root * dir_axial;
+                    if (0.0..=self.height).contains(&y) {
+                        closest = Some((root, (oc_perp + root * dir_perp) / self.radius));
+                        closest_so_far = root;
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Caps: disks at either end, facing away from the cylinder along the axis.
+        if self.capped && dir_axial != 0.0 {
+            for (cap_y, outward_normal) in [(0.0, -self.axis), (self.height, self.axis)] {
+                let root = (cap_y - oc_axial) / dir_axial;
+                if root <= ray_tmin || root >= closest_so_far {
+                    continue;
+                }
+                if (oc_perp + root * dir_perp).length_squared() <= self.radius * self.radius {
+                    closest = Some((root, outward_normal));
+                    closest_so_far = root;
+                }
+            }
+        }
+
+        closest.map(|(root, outward_normal)| {
+            HitRecord::new(r, root, outward_normal, (0.0, 0.0), &self.mat)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::*;
+
+    fn cylinder(capped: bool) -> Cylinder {
+        // Radius one, standing from y = 0 to y = 2 on the origin.
+        let mat = Arc::new(Material::lambertian(Color::new(0.5, 0.5, 0.5)));
+        Cylinder::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            1.0,
+            capped,
+            mat,
+        )
+    }
+
+    fn hit(cylinder: &Cylinder, pos: Vec3, dir: Vec3) -> Option<(f64, Vec3, bool)> {
+        let rec = cylinder.hit(&Ray::new(pos, dir), 0.001, f64::INFINITY)?;
+        Some((rec.t, rec.normal, rec.front_face))
+    }
+
+    #[test]
+    fn side_hits_face_away_from_the_axis() {
+        let (t, normal, front_face) = hit(
+            &cylinder(false),
+            Vec3::new(-5.0, 1.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+        )
+        .expect("hit");
+        assert!((t - 4.0).abs() < 1e-12);
+        assert!((normal - Vec3::new(-1.0, 0.0, 0.0)).near_zero());
+        assert!(front_face);
+
+        // Above the top and below the bottom there's no side.
+        for y in [2.5, -0.5] {
+            let pos = Vec3::new(-5.0, y, 0.0);
+            assert!(hit(&cylinder(true), pos, Vec3::new(1.0, 0.0, 0.0)).is_none());
+        }
+    }
+
+    #[test]
+    fn caps_close_the_ends() {
+        // Straight down onto the top cap, and up onto the bottom one.
+        for (y, dir_y, expected_t, normal_y) in [(5.0, -1.0, 3.0, 1.0), (-5.0, 1.0, 5.0, -1.0)] {
+            let pos = Vec3::new(0.5, y, 0.0);
+            let (t, normal, front_face) =
+                hit(&cylinder(true), pos, Vec3::new(0.0, dir_y, 0.0)).expect("hit");
+            assert!((t - expected_t).abs() < 1e-12);
+            assert!((normal - Vec3::new(0.0, normal_y, 0.0)).near_zero());
+            assert!(front_face);
+        }
+    }
+
+    #[test]
+    fn open_ends_show_the_inside() {
+        // A ray slanting in through the open top hits the inside of the wall, facing back in.
+        let pos = Vec3::new(0.0, 3.0, 0.0);
+        let dir = Vec3::new(0.5, -1.0, 0.0);
+        let (t, normal, front_face) = hit(&cylinder(false), pos, dir).expect("hit");
+        assert!((t - 2.0).abs() < 1e-12);
+        assert!((normal - Vec3::new(-1.0, 0.0, 0.0)).near_zero());
+        assert!(!front_face);
+
+        // With caps, it stops at the top instead; straight down the axis, it misses entirely.
+        let (t, _, _) = hit(&cylinder(true), pos, dir).expect("hit");
+        assert!((t - 1.0).abs() < 1e-12);
+        assert!(hit(&cylinder(false), pos, Vec3::new(0.0, -1.0, 0.0)).is_none());
+    }
+}
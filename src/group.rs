@@ -0,0 +1,103 @@
+use crate::aabb::*;
+use crate::hit_record::*;
+use crate::object::*;
+use crate::ray::*;
+use crate::transform::*;
+
+pub struct Group {
+    children: Vec<Object>,
+    transform: Transform, // From the group's own space to its parent's
+    inverse: Transform,
+    bbox: Aabb, // Bounds of every child, in the parent's space
+}
+
+impl Group {
+    pub fn new(transform: Transform) -> Self {
+        // Children are placed in the group's own space, and moved with it by `transform`; groups
+        // in groups are moved by the transforms of all the groups they're in.
+        Self {
+            children: vec![],
+            transform,
+            inverse: transform.inverse(),
+            bbox: Aabb::empty(),
+        }
+    }
+
+    pub fn add<O: Into<Object>>(&mut self, object: O) {
+        let object = object.into();
+        let bbox = self.transform.bounding_box(object.bounding_box());
+        self.bbox = self.bbox.union(bbox);
+        self.children.push(object);
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    pub fn hit<'s>(&'s self, r: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord<'s>> {
+        if !self.bbox.hit(r, ray_tmin, ray_tmax) {
+            return None;
+        }
+
+        // Hit the children with the ray in the group's space. The direction isn't normalized, so
+        // `t` means the same in both spaces.
+        let local_ray = Ray::new_at_time(
+            self.inverse.point(r.pos),
+            self.inverse.vector(r.dir),
+            r.time,
+        );
+
+        let mut hit_rec: Option<HitRecord<'s>> = None;
+        let mut closest_so_far = ray_tmax;
+
+        for child in &self.children {
+            if let Some(rec) = child.hit(&local_ray, ray_tmin, closest_so_far) {
+                closest_so_far = rec.t;
+                hit_rec = Some(rec);
+            }
+        }
+
+        hit_rec.map(|rec| HitRecord {
+            p: self.transform.point(rec.p),
+            normal: self.inverse.transpose_vector(rec.normal).unit(),
+            dpdu: self.transform.vector(rec.dpdu),
+            dpdv: self.transform.vector(rec.dpdv),
+            ..rec
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::*;
+    use crate::material::*;
+    use crate::sphere::*;
+    use crate::vec3::*;
+
+    use std::sync::Arc;
+
+    #[test]
+    fn children_move_with_the_group() {
+        // A unit sphere in a group stretched along X and moved five units away.
+        let mat = Arc::new(Material::lambertian(Color::new(0.5, 0.5, 0.5)));
+        let transform = Transform::scale(Vec3::new(2.0, 1.0, 1.0))
+            .then(Transform::translate(Vec3::new(0.0, 0.0, -5.0)));
+        let mut group = Group::new(transform);
+        group.add(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 1.0, mat));
+
+        let hit = |pos: Vec3, dir: Vec3| group.hit(&Ray::new(pos, dir), 0.001, f64::INFINITY);
+        let rec = hit(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0)).expect("hit");
+        assert!((rec.t - 4.0).abs() < 1e-12);
+        assert!((rec.p - Vec3::new(0.0, 0.0, -4.0)).near_zero());
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).near_zero());
+
+        let rec = hit(Vec3::new(10.0, 0.0, -5.0), Vec3::new(-1.0, 0.0, 0.0)).expect("hit");
+        assert!((rec.t - 8.0).abs() < 1e-12);
+        assert!((rec.normal - Vec3::new(1.0, 0.0, 0.0)).near_zero());
+
+        // Where the sphere was before moving, and just past its stretched end, there's nothing.
+        assert!(hit(Vec3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)).is_none());
+        assert!(hit(Vec3::new(2.1, 5.0, -5.0), Vec3::new(0.0, -1.0, 0.0)).is_none());
+    }
+}
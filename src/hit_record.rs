@@ -1,5 +1,6 @@
 use crate::material::*;
 use crate::ray::*;
+use crate::scene::*;
 use crate::vec3::*;
 
 use std::sync::Arc;
@@ -9,11 +10,22 @@ pub struct HitRecord<'m> {
     pub normal: Vec3,
     pub mat: &'m Arc<Material>,
     pub t: f64,
+    pub u: f64,
+    pub v: f64,
     pub front_face: bool,
+    pub object_id: ObjectId, // Set by the scene, since objects don't know their own IDs
+    pub dpdu: Vec3,          // Rate of change of `p` with `u`, or zero if the surface doesn't say
+    pub dpdv: Vec3,          // Rate of change of `p` with `v`, likewise
 }
 
 impl<'m> HitRecord<'m> {
-    pub fn new(r: &Ray, t: f64, outward_normal: Vec3, mat: &'m Arc<Material>) -> Self {
+    pub fn new(
+        r: &Ray,
+        t: f64,
+        outward_normal: Vec3,
+        (u, v): (f64, f64),
+        mat: &'m Arc<Material>,
+    ) -> Self {
         // NOTE: The parameter `outward_normal` is assumed to have unit length.
 
         let front_face = r.dir.dot(outward_normal) < 0.0;
@@ -27,7 +39,18 @@ impl<'m> HitRecord<'m> {
             },
             mat,
             t,
+            u,
+            v,
             front_face,
+            object_id: 0,
+            dpdu: Vec3::new(0.0, 0.0, 0.0),
+            dpdv: Vec3::new(0.0, 0.0, 0.0),
         }
     }
+
+    pub fn with_derivatives(self, dpdu: Vec3, dpdv: Vec3) -> Self {
+        // Sets how the hit point moves along the surface with its texture coordinates, which
+        // bump maps need to know which way to tilt the normal.
+        Self { dpdu, dpdv, ..self }
+    }
 }
@@ -0,0 +1,26 @@
+pub mod aabb;
+pub mod animate;
+pub mod background;
+pub mod bmp;
+pub mod camera;
+pub mod color;
+pub mod cone;
+pub mod cylinder;
+pub mod group;
+pub mod hit_record;
+pub mod light;
+pub mod material;
+pub mod object;
+pub mod png;
+pub mod pool;
+pub mod post;
+pub mod quad;
+pub mod random;
+pub mod ray;
+pub mod scene;
+pub mod sky;
+pub mod sphere;
+pub mod texture;
+pub mod transform;
+pub mod triangle_mesh;
+pub mod vec3;
@@ -0,0 +1,263 @@
+use crate::color::*;
+use crate::hit_record::*;
+use crate::quad::*;
+use crate::random::*;
+use crate::ray::*;
+use crate::sphere::*;
+use crate::vec3::*;
+
+// Lights closer than this are treated as being this far away, so they don't blow up to infinity.
+const MIN_DISTANCE: f64 = 0.01;
+
+fn inverse_square(d: Vec3) -> f64 {
+    1.0 / d.length_squared().max(MIN_DISTANCE * MIN_DISTANCE)
+}
+
+pub struct PointLight {
+    pub pos: Vec3,    // Position of the light
+    pub color: Color, // Color and intensity of the light as received one unit away from it
+}
+
+pub struct SpotLight {
+    pub pos: Vec3,        // Position of the light
+    pub dir: Vec3,        // Direction the light is pointing in
+    pub color: Color,     // Color and intensity of the light as received one unit away from it
+    pub inner_angle: f64, // Angle from `dir` in degrees within which the light is at full intensity
+    pub outer_angle: f64, // Angle from `dir` in degrees beyond which there's no light at all
+}
+
+impl SpotLight {
+    fn falloff(&self, p: Vec3) -> f64 {
+        // Smoothly fades out the light between the inner and outer cone angles.
+        let cos_angle = (p - self.pos).unit().dot(self.dir.unit());
+        let cos_inner = self.inner_angle.to_radians().cos();
+        let cos_outer = self.outer_angle.to_radians().cos();
+
+        if cos_angle >= cos_inner {
+            1.0
+        } else if cos_angle <= cos_outer {
+            0.0
+        } else {
+            let t = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+            t * t * (3.0 - 2.0 * t)
+        }
+    }
+}
+
+pub enum Light {
+    Point(PointLight),
+    Spot(SpotLight),
+    Sphere(Sphere), // An emissive sphere, which is also part of the scene's geometry
+    Quad(Quad),     // An emissive quad, which is also part of the scene's geometry
+}
+
+impl Light {
+    pub fn pos(&self) -> Vec3 {
+        match self {
+            Self::Point(point) => point.pos,
+            Self::Spot(spot) => spot.pos,
+            Self::Sphere(sphere) => sphere.center(),
+            Self::Quad(quad) => quad.center(),
+        }
+    }
+
+    pub fn radius(&self) -> f64 {
+        // Distance from `pos` to the light's surface, which is zero if it's on the surface.
+        match self {
+            Self::Point(_) | Self::Spot(_) | Self::Quad(_) => 0.0,
+            Self::Sphere(sphere) => sphere.radius(),
+        }
+    }
+
+    pub fn illuminance(&self, p: Vec3) -> Color {
+        // Light arriving at point `p` from this light, ignoring anything in the way.
+        match self {
+            Self::Point(point) => inverse_square(p - point.pos) * point.color,
+            Self::Spot(spot) => (spot.falloff(p) * inverse_square(p - spot.pos)) * spot.color,
+            Self::Sphere(sphere) => {
+                // Outside of it, a sphere lights a point the same as a point light at its center
+                // with the emitted radiance spread over its cross section.
+                let cross_section = std::f64::consts::PI * sphere.radius() * sphere.radius();
+                (cross_section * inverse_square(p - sphere.center()))
+                    * sphere.material().average_emitted()
+            }
+            Self::Quad(quad) => {
+                // Approximated as a point light at its center with the emitted radiance spread
+                // over its area, as seen at an angle from `p`. One-sided lights are dark behind.
+                let d = p - quad.center();
+                if !quad.material().is_two_sided() && quad.normal().dot(d) <= 0.0 {
+                    return Color::new(0.0, 0.0, 0.0);
+                }
+                let cos_theta = quad.normal().dot(d.unit()).abs();
+                (cos_theta * quad.area() * inverse_square(d)) * quad.material().average_emitted()
+            }
+        }
+    }
+
+    pub fn sample_toward(&self, origin: Vec3, rng: &mut Rng) -> Option<(Vec3, f64)> {
+        // A random unit direction from `origin` toward a light with a surface, along with its
+        // probability density per solid angle, for sampling light that comes from it.
+        match self {
+            Self::Point(_) | Self::Spot(_) => None,
+            Self::Sphere(sphere) => Some(sphere.sample_toward(origin, rng)),
+            Self::Quad(quad) => {
+                let (point, pdf) = quad.sample(origin, rng)?;
+                Some(((point - origin).unit(), pdf))
+            }
+        }
+    }
+
+    pub fn pdf_toward(&self, origin: Vec3, dir: Vec3) -> f64 {
+        // The probability density per solid angle of `sample_toward` picking the direction `dir`
+        // from `origin`, which is zero for directions that miss the light.
+        match self {
+            Self::Point(_) | Self::Spot(_) => 0.0,
+            Self::Sphere(sphere) => sphere.pdf_toward(origin, dir),
+            Self::Quad(quad) => quad.pdf_toward(origin, dir),
+        }
+    }
+
+    pub fn hit<'s>(&'s self, r: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord<'s>> {
+        // Hits the surface of a light that has one.
+        match self {
+            Self::Point(_) | Self::Spot(_) => None,
+            Self::Sphere(sphere) => sphere.hit(r, ray_tmin, ray_tmax),
+            Self::Quad(quad) => quad.hit(r, ray_tmin, ray_tmax),
+        }
+    }
+}
+
+impl From<PointLight> for Light {
+    fn from(point: PointLight) -> Self {
+        Self::Point(point)
+    }
+}
+
+impl From<SpotLight> for Light {
+    fn from(spot: SpotLight) -> Self {
+        Self::Spot(spot)
+    }
+}
+
+impl From<Sphere> for Light {
+    fn from(sphere: Sphere) -> Self {
+        Self::Sphere(sphere)
+    }
+}
+
+impl From<Quad> for Light {
+    fn from(quad: Quad) -> Self {
+        Self::Quad(quad)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::*;
+
+    use std::sync::Arc;
+
+    #[test]
+    fn sphere_lights_are_sampled_over_their_cone() {
+        // Sampled directions all hit the sphere, with a density of one over the solid angle of
+        // the cone it subtends, which uniformly random directions hit that fraction of the time.
+        let mat = Arc::new(Material::diffuse_light(Color::new(1.0, 1.0, 1.0)));
+        let light = Light::from(Sphere::new(Vec3::new(1.0, 2.0, -3.0), 1.5, mat));
+        let origin = Vec3::new(0.5, -1.0, 1.0);
+        let mut rng = Rng::new(1);
+
+        let (_, pdf) = light
+            .sample_toward(origin, &mut rng)
+            .expect("sample_toward");
+        for _ in 0..1000 {
+            let (dir, sample_pdf) = light
+                .sample_toward(origin, &mut rng)
+                .expect("sample_toward");
+            assert_eq!(sample_pdf, pdf);
+            assert!((dir.length() - 1.0).abs() < 1e-12);
+            assert!(
+                light
+                    .hit(&Ray::new(origin, dir), 0.0, f64::INFINITY)
+                    .is_some()
+            );
+            assert!((light.pdf_toward(origin, dir) - pdf).abs() < 1e-9 * pdf);
+        }
+
+        let n = 200_000;
+        let hits = (0..n)
+            .map(|_| Vec3::random_unit_vector(&mut rng))
+            .filter(|&dir| {
+                light
+                    .hit(&Ray::new(origin, dir), 0.0, f64::INFINITY)
+                    .is_some()
+            })
+            .count();
+        let solid_angle = 4.0 * std::f64::consts::PI * hits as f64 / n as f64;
+        assert!((solid_angle * pdf - 1.0).abs() < 0.03);
+        assert_eq!(light.pdf_toward(origin, -(light.pos() - origin)), 0.0);
+
+        // From inside, every direction hits it.
+        let inside = light.pos() + Vec3::new(0.5, 0.0, 0.0);
+        let (_, pdf) = light
+            .sample_toward(inside, &mut rng)
+            .expect("sample_toward");
+        assert_eq!(pdf, 1.0 / (4.0 * std::f64::consts::PI));
+    }
+
+    #[test]
+    fn spot_lights_fade_between_their_cones() {
+        // Full inside 20 degrees, dark beyond 30, and smoothly and steadily fading between.
+        let spot = SpotLight {
+            pos: Vec3::new(0.0, 2.0, 0.0),
+            dir: Vec3::new(0.0, -3.0, 0.0),
+            color: Color::new(1.0, 1.0, 1.0),
+            inner_angle: 20.0,
+            outer_angle: 30.0,
+        };
+        let at = |degrees: f64| {
+            let (sin, cos) = degrees.to_radians().sin_cos();
+            spot.pos + Vec3::new(sin, -cos, 0.0)
+        };
+        assert_eq!(spot.falloff(at(0.0)), 1.0);
+        assert_eq!(spot.falloff(at(19.99)), 1.0);
+        assert_eq!(spot.falloff(at(30.01)), 0.0);
+        assert_eq!(spot.falloff(at(90.0)), 0.0);
+        assert!(spot.falloff(at(20.01)) > 0.999 && spot.falloff(at(29.99)) < 0.001);
+
+        let mut last = 1.0;
+        for degrees in (21..30).map(f64::from) {
+            let falloff = spot.falloff(at(degrees));
+            assert!(0.0 < falloff && falloff < last);
+            last = falloff;
+        }
+
+        // One unit from the light, illuminance is just the color scaled by the falloff.
+        let p = at(25.0);
+        let falloff = spot.falloff(p);
+        let light = Light::from(spot);
+        assert!((light.illuminance(p).g() - falloff).abs() < 1e-12);
+    }
+
+    #[test]
+    fn point_lights_fall_off_with_the_square_of_distance() {
+        // Twice as far is a quarter as bright, in any direction, until the light is so close it
+        // would blow up.
+        let light = Light::from(PointLight {
+            pos: Vec3::new(1.0, 2.0, 3.0),
+            color: Color::new(2.0, 1.0, 0.5),
+        });
+        for dir in [Vec3::new(1.0, 0.0, 0.0), Vec3::new(-1.0, 2.0, 2.0).unit()] {
+            for distance in [0.5, 1.0, 2.0, 4.0] {
+                let c = light.illuminance(light.pos() + distance * dir);
+                let expected = 1.0 / (distance * distance);
+                assert!((c.r() - 2.0 * expected).abs() < 1e-12 * expected);
+                assert!((c.b() - 0.5 * expected).abs() < 1e-12 * expected);
+            }
+        }
+        let closest = light.illuminance(light.pos() + Vec3::new(0.0, MIN_DISTANCE, 0.0));
+        let closer = light.illuminance(light.pos() + Vec3::new(0.0, 0.1 * MIN_DISTANCE, 0.0));
+        assert_eq!(closer.g(), closest.g());
+        assert!(closer.g().is_finite());
+    }
+}
@@ -1,24 +1,10 @@
-mod camera;
-mod color;
-mod hit_record;
-mod material;
-mod random;
-mod ray;
-mod scene;
-mod sphere;
-mod vec3;
-
-use camera::*;
-use color::*;
-use material::*;
-use random::*;
-use scene::*;
-use sphere::*;
-use vec3::*;
+use raytracing::camera::*;
+use raytracing::random::*;
+use raytracing::scene::*;
 
 use miniquad::{
-    Bindings, BufferSource, BufferType, BufferUsage, EventHandler, FilterMode, GlContext, KeyCode,
-    KeyMods, Pipeline, RenderingBackend, UniformsSource,
+    Bindings, BufferId, BufferSource, BufferType, BufferUsage, EventHandler, FilterMode, GlContext,
+    KeyCode, KeyMods, Pipeline, RenderingBackend, UniformsSource,
 };
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -54,96 +40,53 @@ fn calc_zoom(
     }
 }
 
+fn view_bindings(
+    gfx: &mut GlContext,
+    camera: &Camera,
+    quad_vbuf: BufferId,
+    quad_ibuf: BufferId,
+) -> Vec<Bindings> {
+    let mut bindings: Vec<Bindings> = vec![];
+    let image_height = camera.get_height() as u16;
+
+    camera.for_each_view(|_, _, view_width, pixel_buf| {
+        let texture = gfx.new_texture_from_rgba8(view_width as u16, image_height, pixel_buf);
+        gfx.texture_set_mag_filter(texture, FilterMode::Nearest);
+
+        bindings.push(Bindings {
+            vertex_buffers: vec![quad_vbuf],
+            index_buffer: quad_ibuf,
+            images: vec![texture],
+        });
+    });
+
+    bindings
+}
+
 struct App {
     gfx: GlContext,
     pipeline: Pipeline,
+    quad_vbuf: BufferId,
+    quad_ibuf: BufferId,
     bindings: Vec<Bindings>,
     zoom: [f32; 2],
+    window_size: [f32; 2],
+    paused: bool,
+    render_at_window_size: bool,
+    bbox_overlay: bool,
     camera: Camera,
 }
 
 impl App {
-    fn new() -> Self {
-        let mut rng = Rng::new(miniquad::date::now() as _);
-
-        // Scene
-
-        let mut scene = Scene::new();
-
-        let ground_material = Arc::new(Material::lambertian(Color::new(0.5, 0.5, 0.5)));
-        scene.add(Sphere::new(
-            Vec3::new(0.0, -1000.0, 0.0),
-            1000.0,
-            ground_material,
-        ));
-
-        for a in -11..11 {
-            for b in -11..11 {
-                let center = Vec3::new(
-                    a as f64 + 0.9 * rng.random_f64(),
-                    0.2,
-                    b as f64 + 0.9 * rng.random_f64(),
-                );
-
-                if (center - Vec3::new(4.0, 0.2, 0.0)).length() <= 0.9 {
-                    continue;
-                }
-
-                let choose_mat = rng.random_f64();
-                let sphere_material: Arc<Material> = if choose_mat < 0.8 {
-                    // diffuse
-                    let albedo = Color::from_vec3(Vec3::random(&mut rng))
-                        * Color::from_vec3(Vec3::random(&mut rng));
-                    Arc::new(Material::lambertian(albedo))
-                } else if choose_mat < 0.95 {
-                    // metal
-                    let albedo = Color::from_vec3(Vec3::random_range(&mut rng, 0.5, 1.0));
-                    let fuzz = rng.random_f64_range(0.0, 0.5);
-                    Arc::new(Material::metal(albedo, fuzz))
-                } else {
-                    // glass
-                    Arc::new(Material::dielectric(1.5))
-                };
-
-                scene.add(Sphere::new(center, 0.2, sphere_material));
-            }
-        }
-
-        let material1 = Arc::new(Material::dielectric(1.5));
-        scene.add(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, material1));
-
-        let material2 = Arc::new(Material::lambertian(Color::new(0.4, 0.2, 0.1)));
-        scene.add(Sphere::new(Vec3::new(-4.0, 1.0, 0.0), 1.0, material2));
-
-        let material3 = Arc::new(Material::metal(Color::new(0.7, 0.6, 0.5), 0.0));
-        scene.add(Sphere::new(Vec3::new(4.0, 1.0, 0.0), 1.0, material3));
-
-        // Camera
-
-        let threads = std::env::args()
-            .nth(1)
-            .and_then(|a| a.parse::<u8>().ok())
-            .unwrap_or(4);
-
-        let image_width: u16 = 1200;
-
+    fn new(scene: Scene, camera_options: CameraOptions, threads: u8) -> Self {
         let camera = Camera::new(
             &Arc::new(scene),
             miniquad::date::now() as _,
             threads,
-            CameraOptions {
-                aspect_ratio: 16.0 / 9.0,
-                image_width,
-                max_depth: 50,
-                vfov: 20.0,
-                lookfrom: Vec3::new(13.0, 2.0, 3.0),
-                lookat: Vec3::new(0.0, 0.0, 0.0),
-                vup: Vec3::new(0.0, 1.0, 0.0),
-                defocus_angle: 0.6,
-                focus_dist: 10.0,
-            },
+            camera_options,
         );
 
+        let image_width = camera.get_width() as u16;
         let image_height = camera.get_height() as u16;
 
         // App Setup
@@ -171,22 +114,13 @@ impl App {
             BufferSource::slice(&quad_ibuf_data),
         );
 
-        let mut bindings: Vec<Bindings> = vec![];
-
-        camera.for_each_view(|_, _, view_width, pixel_buf| {
-            let texture = gfx.new_texture_from_rgba8(view_width as u16, image_height, pixel_buf);
-            gfx.texture_set_mag_filter(texture, FilterMode::Nearest);
-
-            bindings.push(Bindings {
-                vertex_buffers: vec![quad_vbuf],
-                index_buffer: quad_ibuf,
-                images: vec![texture],
-            });
-        });
+        let bindings = view_bindings(&mut gfx, &camera, quad_vbuf, quad_ibuf);
 
         Self {
             gfx,
             pipeline,
+            quad_vbuf,
+            quad_ibuf,
             bindings,
             zoom: calc_zoom(
                 image_width as f32,
@@ -194,9 +128,38 @@ impl App {
                 LAUNCH_WIDTH as f32,
                 LAUNCH_HEIGHT as f32,
             ),
+            window_size: [LAUNCH_WIDTH as f32, LAUNCH_HEIGHT as f32],
+            paused: false,
+            render_at_window_size: false,
+            bbox_overlay: false,
             camera,
         }
     }
+
+    fn update_textures(&mut self) {
+        self.camera.for_each_view(|i, _, _, pixel_buf| {
+            self.gfx
+                .texture_update(self.bindings[i].images[0], pixel_buf);
+        });
+    }
+
+    fn resize_camera(&mut self) {
+        // Re-render at the window's width, replacing the textures of the resized views.
+        self.camera
+            .resize(self.window_size[0].clamp(1.0, u16::MAX as f32) as u16);
+
+        for bindings in self.bindings.drain(..) {
+            self.gfx.delete_texture(bindings.images[0]);
+        }
+        self.bindings = view_bindings(&mut self.gfx, &self.camera, self.quad_vbuf, self.quad_ibuf);
+
+        self.zoom = calc_zoom(
+            self.camera.get_width() as f32,
+            self.camera.get_height() as f32,
+            self.window_size[0],
+            self.window_size[1],
+        );
+    }
 }
 
 impl EventHandler for App {
@@ -223,21 +186,44 @@ impl EventHandler for App {
     }
 
     fn update(&mut self) {
+        if self.paused || self.camera.is_complete() {
+            return;
+        }
+
         let until = Instant::now() + Duration::from_micros(950_000 / 60);
         self.camera.render(until);
-        self.camera.for_each_view(|i, _, _, pixel_buf| {
-            self.gfx
-                .texture_update(self.bindings[i].images[0], pixel_buf);
-        });
+        self.update_textures();
     }
 
-    fn key_down_event(&mut self, keycode: KeyCode, _keymods: KeyMods, _repeat: bool) {
-        if keycode == KeyCode::Escape {
-            miniquad::window::request_quit();
+    fn key_down_event(&mut self, keycode: KeyCode, _keymods: KeyMods, repeat: bool) {
+        match keycode {
+            KeyCode::Escape => miniquad::window::request_quit(),
+            KeyCode::P if !repeat => {
+                self.paused = !self.paused;
+                self.camera.set_paused(self.paused);
+            }
+            KeyCode::R if !repeat => {
+                self.render_at_window_size = !self.render_at_window_size;
+                if self.render_at_window_size {
+                    self.resize_camera();
+                }
+            }
+            KeyCode::B if !repeat => {
+                self.bbox_overlay = !self.bbox_overlay;
+                self.camera.set_bbox_overlay(self.bbox_overlay);
+                self.update_textures();
+            }
+            _ => {}
         }
     }
 
     fn resize_event(&mut self, width: f32, height: f32) {
+        self.window_size = [width, height];
+        if self.render_at_window_size {
+            self.resize_camera();
+            return;
+        }
+
         self.zoom = calc_zoom(
             self.camera.get_width() as f32,
             self.camera.get_height() as f32,
@@ -248,6 +234,30 @@ impl EventHandler for App {
 }
 
 fn main() {
+    // Arguments are a number of threads, which defaults to four, and `--scene` with the name of
+    // a built-in scene, which defaults to the demo scene.
+    let mut threads = 4;
+    let mut scene_name = String::from("demo");
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--scene" {
+            scene_name = args.next().unwrap_or_default();
+        } else if let Ok(n) = arg.parse::<u8>() {
+            threads = n;
+        }
+    }
+
+    let Some(scene_fn) = built_in_scene(&scene_name) else {
+        let names: Vec<&str> = BUILT_IN_SCENES.iter().map(|&(name, _)| name).collect();
+        eprintln!(
+            "unknown scene `{scene_name}`; available scenes: {}",
+            names.join(", ")
+        );
+        std::process::exit(1);
+    };
+    let mut rng = Rng::new(miniquad::date::now() as _);
+    let (scene, camera_options) = scene_fn(&mut rng);
+
     miniquad::start(
         miniquad::conf::Conf {
             window_title: String::from("raytracing"),
@@ -255,7 +265,7 @@ fn main() {
             window_height: LAUNCH_HEIGHT,
             ..Default::default()
         },
-        || Box::new(App::new()),
+        move || Box::new(App::new(scene, camera_options, threads)),
     );
 }
 
@@ -2,13 +2,128 @@ use crate::color::*;
 use crate::hit_record::*;
 use crate::random::*;
 use crate::ray::*;
+use crate::scene::*;
+use crate::texture::*;
 use crate::vec3::*;
 
+use std::sync::Arc;
+
 pub struct ScatterRecord {
     pub attenuation: Color,
     pub scattered: Ray,
 }
 
+// Bump maps tilt normals by no more than the angle with this cosine, about 78 degrees, so they
+// can't end up facing the other way.
+const MIN_BUMP_COS: f64 = 0.2;
+
+pub struct BumpMap {
+    height: Arc<ImageTexture>, // Height above the surface by luminance, before scaling
+    scale: f64,                // Height of a white texel, in scene units
+}
+
+impl BumpMap {
+    fn normal(&self, rec: &HitRecord) -> Vec3 {
+        // The normal of the surface if it were moved out along its normal by the height at each
+        // point, found from the height's slopes and how the surface moves with `u` and `v`.
+        // Surfaces that don't say how get an arbitrary frame with `u` and `v` a unit apart.
+        let outward = if rec.front_face {
+            rec.normal
+        } else {
+            -rec.normal
+        };
+        let (dpdu, dpdv) = if rec.dpdu.near_zero() || rec.dpdv.near_zero() {
+            let a = if outward.x().abs() > 0.9 {
+                Vec3::new(0.0, 1.0, 0.0)
+            } else {
+                Vec3::new(1.0, 0.0, 0.0)
+            };
+            let dpdu = outward.cross(a).unit();
+            (dpdu, outward.cross(dpdu))
+        } else {
+            (rec.dpdu, rec.dpdv)
+        };
+
+        let (dhdu, dhdv) = self.height.slope(rec.u, rec.v);
+        let n = (dpdu + (self.scale * dhdu) * outward).cross(dpdv + (self.scale * dhdv) * outward);
+        if n.near_zero() {
+            return rec.normal;
+        }
+
+        // Surfaces may be parameterized either way around, so orient by the unbumped normal.
+        let n = dpdu.cross(dpdv).dot(outward).signum() * n.unit();
+        let cos = n.dot(outward);
+        let n = if cos >= MIN_BUMP_COS {
+            n
+        } else {
+            let sideways = n - cos * outward;
+            if sideways.near_zero() {
+                outward
+            } else {
+                MIN_BUMP_COS * outward
+                    + (1.0 - MIN_BUMP_COS * MIN_BUMP_COS).sqrt() * sideways.unit()
+            }
+        };
+
+        if rec.front_face { n } else { -n }
+    }
+}
+
+// Paths can be inside this many dielectrics at once; surfaces of any more are passed through.
+const MAX_MEDIA: usize = 8;
+
+// The dielectrics a path is inside of, by object, so it's known which medium is on each side of
+// the next surface it crosses. Objects in groups share their group's ID, so a group should hold
+// no more than one dielectric that others may be inside of.
+#[derive(Copy, Clone, Default)]
+pub struct Media {
+    entries: [(ObjectId, u8, f64); MAX_MEDIA], // Object, priority and refraction index
+    len: usize,
+}
+
+impl Media {
+    fn current(&self) -> Option<(ObjectId, u8, f64)> {
+        // The medium the path is in, which is the highest priority one, or the one entered last
+        // among equals.
+        self.entries[..self.len]
+            .iter()
+            .copied()
+            .fold(None, |best, entry| match best {
+                Some(best) if best.1 > entry.1 => Some(best),
+                _ => Some(entry),
+            })
+    }
+
+    pub fn refraction_index(&self) -> f64 {
+        // Outside of every dielectric is a vacuum.
+        self.current()
+            .map_or(1.0, |(_, _, refraction_index)| refraction_index)
+    }
+
+    fn contains(&self, id: ObjectId) -> bool {
+        self.entries[..self.len].iter().any(|entry| entry.0 == id)
+    }
+
+    fn entered(mut self, entry: (ObjectId, u8, f64)) -> Self {
+        if self.len < MAX_MEDIA {
+            self.entries[self.len] = entry;
+            self.len += 1;
+        }
+        self
+    }
+
+    fn left(mut self, id: ObjectId) -> Self {
+        if let Some(i) = self.entries[..self.len]
+            .iter()
+            .rposition(|entry| entry.0 == id)
+        {
+            self.entries.copy_within(i + 1..self.len, i);
+            self.len -= 1;
+        }
+        self
+    }
+}
+
 fn reflectance(cosine: f64, refraction_index: f64) -> f64 {
     // Use Schlick's approximation for reflectance.
     let mut r0 = (1.0 - refraction_index) / (1.0 + refraction_index);
@@ -16,63 +131,377 @@ fn reflectance(cosine: f64, refraction_index: f64) -> f64 {
     r0 + (1.0 - r0) * (1.0 - cosine).powf(5.0)
 }
 
+fn scatter_origin(rec: &HitRecord, dir: Vec3) -> Vec3 {
+    // Start scattered rays just off the surface on the side they're leaving towards, so rounding
+    // errors in `rec.p`, which grow with its distance from the origin, don't put them behind it.
+    let scale = rec.p.iter().fold(1.0, |m: f64, x| m.max(x.abs()));
+    let offset = 1.0e-7 * scale * rec.normal;
+    if dir.dot(rec.normal) >= 0.0 {
+        rec.p + offset
+    } else {
+        rec.p - offset
+    }
+}
+
+fn lambertian_scatter(rng: &mut Rng, r_in: &Ray, rec: &HitRecord, albedo: Color) -> ScatterRecord {
+    let mut scatter_direction = rec.normal + Vec3::random_unit_vector(rng);
+
+    // Catch degenerate scatter direction.
+    if scatter_direction.near_zero() {
+        scatter_direction = rec.normal;
+    }
+
+    ScatterRecord {
+        attenuation: albedo,
+        scattered: Ray::new_at_time(
+            scatter_origin(rec, scatter_direction),
+            scatter_direction,
+            r_in.time,
+        ),
+    }
+}
+
 pub enum Material {
-    Lambertian { albedo: Color },
-    Metal { albedo: Color, fuzz: f64 },
-    Dieletric { refraction_index: f64 },
+    Lambertian {
+        albedo: Color,
+        bump: Option<BumpMap>,
+    },
+    Textured {
+        texture: Arc<ImageTexture>,
+        bump: Option<BumpMap>,
+    },
+    Metal {
+        albedo: Color,
+        fuzz: f64,
+        bump: Option<BumpMap>,
+    },
+    Dieletric {
+        refraction_index: f64,
+        dispersion: f64,
+        priority: u8, // Which of overlapping dielectrics light sees, the highest first
+        bump: Option<BumpMap>,
+    },
+    DiffuseLight {
+        emit: Color,
+        texture: Option<Arc<ImageTexture>>, // Multiplies `emit` by its color at each point
+        two_sided: bool,                    // Emits from back faces too
+    },
+    ShadowCatcher,
 }
 
 impl Material {
     pub fn lambertian(albedo: Color) -> Self {
-        Self::Lambertian { albedo }
+        Self::Lambertian { albedo, bump: None }
+    }
+
+    pub fn textured(texture: Arc<ImageTexture>) -> Self {
+        Self::Textured {
+            texture,
+            bump: None,
+        }
     }
 
     pub fn metal(albedo: Color, fuzz: f64) -> Self {
-        Self::Metal { albedo, fuzz }
+        Self::Metal {
+            albedo,
+            fuzz,
+            bump: None,
+        }
     }
 
     pub fn dielectric(refraction_index: f64) -> Self {
-        Self::Dieletric { refraction_index }
+        Self::Dieletric {
+            refraction_index,
+            dispersion: 0.0,
+            priority: 0,
+            bump: None,
+        }
     }
 
-    pub fn scatter(&self, rng: &mut Rng, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+    pub fn dispersive_dielectric(refraction_index: f64, dispersion: f64) -> Self {
+        // Like glass whose refraction index is `dispersion` higher for blue than for red light,
+        // with `refraction_index` being the index for green.
+        Self::Dieletric {
+            refraction_index,
+            dispersion,
+            priority: 0,
+            bump: None,
+        }
+    }
+
+    pub fn diffuse_light(emit: Color) -> Self {
+        Self::DiffuseLight {
+            emit,
+            texture: None,
+            two_sided: true,
+        }
+    }
+
+    pub fn textured_light(texture: Arc<ImageTexture>, emit: Color) -> Self {
+        // Emits the texture's image scaled by `emit`, like a glowing screen.
+        Self::DiffuseLight {
+            emit,
+            texture: Some(texture),
+            two_sided: true,
+        }
+    }
+
+    pub fn one_sided(self) -> Self {
+        // Makes a diffuse light emit only from its front face, like a panel against a ceiling.
         match self {
-            Self::Lambertian { albedo } => {
-                let mut scatter_direction = rec.normal + Vec3::random_unit_vector(rng);
+            Self::DiffuseLight { emit, texture, .. } => Self::DiffuseLight {
+                emit,
+                texture,
+                two_sided: false,
+            },
+            _ => panic!("only diffuse lights can be made one-sided"),
+        }
+    }
 
-                // Catch degenerate scatter direction.
-                if scatter_direction.near_zero() {
-                    scatter_direction = rec.normal;
-                }
+    pub fn is_two_sided(&self) -> bool {
+        !matches!(
+            self,
+            Self::DiffuseLight {
+                two_sided: false,
+                ..
+            }
+        )
+    }
 
-                Some(ScatterRecord {
-                    attenuation: *albedo,
-                    scattered: Ray {
-                        pos: rec.p,
-                        dir: scatter_direction,
-                    },
-                })
+    pub fn with_bump(self, height: Arc<ImageTexture>, scale: f64) -> Self {
+        // Shades the surface as if it were raised by `scale` times the luminance of `height` at
+        // each point, which tilts its normals without moving it.
+        let bump = Some(BumpMap { height, scale });
+        match self {
+            Self::Lambertian { albedo, .. } => Self::Lambertian { albedo, bump },
+            Self::Textured { texture, .. } => Self::Textured { texture, bump },
+            Self::Metal { albedo, fuzz, .. } => Self::Metal { albedo, fuzz, bump },
+            Self::Dieletric {
+                refraction_index,
+                dispersion,
+                priority,
+                ..
+            } => Self::Dieletric {
+                refraction_index,
+                dispersion,
+                priority,
+                bump,
+            },
+            _ => panic!("only materials that scatter light can be bump mapped"),
+        }
+    }
+
+    pub fn with_priority(self, priority: u8) -> Self {
+        // Where dielectrics overlap, light only sees the one with the highest priority, so e.g.
+        // water can overlap the glass holding it. Dielectrics that are wholly inside others
+        // don't need one; they're all zero to begin with.
+        match self {
+            Self::Dieletric {
+                refraction_index,
+                dispersion,
+                bump,
+                ..
+            } => Self::Dieletric {
+                refraction_index,
+                dispersion,
+                priority,
+                bump,
+            },
+            _ => panic!("only dielectrics have a priority"),
+        }
+    }
+
+    pub fn shading_normal(&self, rec: &HitRecord) -> Vec3 {
+        // The normal to shade the hit with, tilted by the bump map if there is one.
+        match self {
+            Self::Lambertian {
+                bump: Some(bump), ..
             }
-            Self::Metal { albedo, fuzz } => {
+            | Self::Textured {
+                bump: Some(bump), ..
+            }
+            | Self::Metal {
+                bump: Some(bump), ..
+            }
+            | Self::Dieletric {
+                bump: Some(bump), ..
+            } => bump.normal(rec),
+            _ => rec.normal,
+        }
+    }
+
+    pub fn shadow_catcher() -> Self {
+        // Shows only the background, darkened where the scene's lights are blocked, with an
+        // alpha of how much it's darkened so the shadows can be composited onto other images.
+        Self::ShadowCatcher
+    }
+
+    pub fn albedo(&self) -> Color {
+        // Base color without scattering; textures give their average, dielectrics are clear.
+        match self {
+            Self::Lambertian { albedo, .. } => *albedo,
+            Self::Textured { texture, .. } => texture.average(),
+            Self::Metal { albedo, .. } => *albedo,
+            Self::Dieletric { .. } => Color::new(1.0, 1.0, 1.0),
+            Self::DiffuseLight { .. } => self.average_emitted(),
+            Self::ShadowCatcher => Color::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn is_diffuse(&self) -> bool {
+        matches!(self, Self::Lambertian { .. } | Self::Textured { .. })
+    }
+
+    pub fn emitted(&self, u: f64, v: f64, _p: Vec3, front_face: bool) -> Color {
+        // Light given off at texture coordinates `u`, `v` of the hit point `p`, on the front face
+        // or not; no emitters vary with position in space yet.
+        match self {
+            Self::DiffuseLight {
+                two_sided: false, ..
+            } if !front_face => Color::new(0.0, 0.0, 0.0),
+            Self::DiffuseLight {
+                emit,
+                texture: Some(texture),
+                ..
+            } => *emit * texture.value(u, v),
+            Self::DiffuseLight { emit, .. } => *emit,
+            _ => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    pub fn average_emitted(&self) -> Color {
+        // Light given off on average over the whole surface, for estimates of how much there is.
+        match self {
+            Self::DiffuseLight {
+                emit,
+                texture: Some(texture),
+                ..
+            } => *emit * texture.average(),
+            Self::DiffuseLight { emit, .. } => *emit,
+            _ => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    pub fn scatter(&self, rng: &mut Rng, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        // Scatters as though there's a vacuum on the outward side of the surface.
+        self.scatter_beside(rng, r_in, rec, 1.0)
+    }
+
+    pub fn scatter_through(
+        &self,
+        rng: &mut Rng,
+        r_in: &Ray,
+        rec: &HitRecord,
+        media: &Media,
+    ) -> Option<(ScatterRecord, Media)> {
+        // Like `scatter` for a ray inside `media`, also giving the media the scattered ray is
+        // inside. Dielectrics bend light by the ratio of the refraction indices on either side
+        // of the surface, unless it's inside one of higher priority, where it isn't really there
+        // and the ray passes straight through.
+        let &Self::Dieletric {
+            refraction_index,
+            priority,
+            ..
+        } = self
+        else {
+            return self.scatter(rng, r_in, rec).map(|sc_rec| (sc_rec, *media));
+        };
+
+        // The media on either side of the surface. A ray can leave a dielectric it wasn't known
+        // to be in, e.g. if it started there, which is as though it had just entered it.
+        let id = rec.object_id;
+        let (inside, outside) = if !rec.front_face && media.contains(id) {
+            (*media, media.left(id))
+        } else {
+            (media.entered((id, priority, refraction_index)), *media)
+        };
+        let crossed = if rec.front_face { inside } else { outside };
+
+        if inside.current().is_none_or(|(current, ..)| current != id) {
+            let scattered = Ray {
+                channel: r_in.channel,
+                ..Ray::new_at_time(scatter_origin(rec, r_in.dir), r_in.dir, r_in.time)
+            };
+            let sc_rec = ScatterRecord {
+                attenuation: Color::new(1.0, 1.0, 1.0),
+                scattered,
+            };
+            return Some((sc_rec, crossed));
+        }
+
+        // Only refracted rays cross to the other side of the surface.
+        let sc_rec = self.scatter_beside(rng, r_in, rec, outside.refraction_index())?;
+        let media = if sc_rec.scattered.dir.dot(rec.normal) < 0.0 {
+            crossed
+        } else {
+            *media
+        };
+        Some((sc_rec, media))
+    }
+
+    fn scatter_beside(
+        &self,
+        rng: &mut Rng,
+        r_in: &Ray,
+        rec: &HitRecord,
+        outside_index: f64,
+    ) -> Option<ScatterRecord> {
+        // Scatters with a medium of refraction index `outside_index` on the outward side of the
+        // surface, which only dielectrics care about. Scattered rays carry the same color
+        // channel as `r_in`, unless dispersive glass picks one for them.
+        let mut sc_rec = match self {
+            Self::Lambertian { albedo, .. } => Some(lambertian_scatter(rng, r_in, rec, *albedo)),
+            Self::Textured { texture, .. } => Some(lambertian_scatter(
+                rng,
+                r_in,
+                rec,
+                texture.value(rec.u, rec.v),
+            )),
+            Self::Metal { albedo, fuzz, .. } => {
                 let mut reflected = r_in.dir.reflect(rec.normal);
                 reflected = reflected.unit() + *fuzz * Vec3::random_unit_vector(rng);
                 if reflected.dot(rec.normal) > 0.0 {
                     Some(ScatterRecord {
                         attenuation: *albedo,
-                        scattered: Ray {
-                            pos: rec.p,
-                            dir: reflected,
-                        },
+                        scattered: Ray::new_at_time(
+                            scatter_origin(rec, reflected),
+                            reflected,
+                            r_in.time,
+                        ),
                     })
                 } else {
                     None
                 }
             }
-            Self::Dieletric { refraction_index } => {
+            Self::Dieletric {
+                refraction_index,
+                dispersion,
+                ..
+            } => {
+                // Dispersive glass refracts one randomly chosen color channel with its own index,
+                // weighted by three so the average over the channels is the same. The ray keeps
+                // that channel from then on, so later dispersive hits refract it alike instead of
+                // choosing again, which would leave a path only a 1 in 3^k chance of any light.
+                let (refraction_index, attenuation, channel) = if *dispersion == 0.0 {
+                    (*refraction_index, Color::new(1.0, 1.0, 1.0), r_in.channel)
+                } else {
+                    let (channel, weight) = match r_in.channel {
+                        Some(channel) => (channel, 1.0),
+                        None => (rng.random_index(3), 3.0),
+                    };
+                    let mut attenuation = [0.0; 3];
+                    attenuation[channel] = weight;
+                    (
+                        refraction_index + (channel as f64 - 1.0) * dispersion / 2.0,
+                        Color::from_vec3(attenuation.into()),
+                        Some(channel),
+                    )
+                };
+
                 let ri = if rec.front_face {
-                    1.0 / *refraction_index
+                    outside_index / refraction_index
                 } else {
-                    *refraction_index
+                    refraction_index / outside_index
                 };
 
                 let unit_direction = r_in.dir.unit();
@@ -87,13 +516,255 @@ impl Material {
                 };
 
                 Some(ScatterRecord {
-                    attenuation: Color::new(1.0, 1.0, 1.0),
+                    attenuation,
                     scattered: Ray {
-                        pos: rec.p,
-                        dir: direction,
+                        channel,
+                        ..Ray::new_at_time(scatter_origin(rec, direction), direction, r_in.time)
                     },
                 })
             }
+            Self::DiffuseLight { .. } | Self::ShadowCatcher => None,
+        }?;
+        sc_rec.scattered.channel = sc_rec.scattered.channel.or(r_in.channel);
+        Some(sc_rec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit_plane<'m>(r: &Ray, mat: &'m Arc<Material>) -> HitRecord<'m> {
+        // Where `r` crosses the plane z = 0, which faces +Z.
+        let t = -r.pos.z() / r.dir.z();
+        HitRecord::new(r, t, Vec3::new(0.0, 0.0, 1.0), (0.0, 0.0), mat)
+    }
+
+    #[test]
+    fn dispersion_keeps_the_channel_it_picks() {
+        let glass = Arc::new(Material::dispersive_dielectric(1.5, 0.1));
+        let clay = Arc::new(Material::lambertian(Color::new(0.5, 0.5, 0.5)));
+        let mut rng = Rng::new(1);
+        let r = Ray::new(Vec3::new(-1.0, 0.0, 1.0), Vec3::new(1.0, 0.0, -1.0));
+
+        for _ in 0..100 {
+            // The first dispersive hit picks a channel and weights it by three...
+            let first = glass
+                .scatter(&mut rng, &r, &hit_plane(&r, &glass))
+                .expect("scatter");
+            let channel = first.scattered.channel.expect("channel");
+            let weights = [
+                first.attenuation.r(),
+                first.attenuation.g(),
+                first.attenuation.b(),
+            ];
+            assert_eq!(weights[channel], 3.0);
+            assert_eq!(weights.iter().sum::<f64>(), 3.0);
+
+            // ... which later hits keep, dispersive or not, without weighting it again.
+            let r = Ray {
+                channel: Some(channel),
+                ..Ray::new(r.pos, r.dir)
+            };
+            let again = glass
+                .scatter(&mut rng, &r, &hit_plane(&r, &glass))
+                .expect("scatter");
+            assert_eq!(again.scattered.channel, Some(channel));
+            let weights = [
+                again.attenuation.r(),
+                again.attenuation.g(),
+                again.attenuation.b(),
+            ];
+            assert_eq!(weights[channel], 1.0);
+            assert_eq!(weights.iter().sum::<f64>(), 1.0);
+
+            let diffuse = clay
+                .scatter(&mut rng, &r, &hit_plane(&r, &clay))
+                .expect("scatter");
+            assert_eq!(diffuse.scattered.channel, Some(channel));
+        }
+    }
+
+    #[test]
+    fn media_enter_and_leave_by_priority() {
+        let empty = Media::default();
+        assert_eq!(empty.refraction_index(), 1.0);
+
+        // Among equal priorities, the medium entered last is the one the path is in.
+        let glass = empty.entered((1, 0, 1.5));
+        let water = glass.entered((2, 0, 1.33));
+        assert_eq!(water.refraction_index(), 1.33);
+        assert_eq!(water.left(2).refraction_index(), 1.5);
+        assert_eq!(water.left(1).refraction_index(), 1.33);
+        assert_eq!(water.left(1).left(2).refraction_index(), 1.0);
+
+        // A higher priority wins wherever the media overlap, whichever was entered first.
+        let glass = empty.entered((1, 2, 1.5));
+        let water = glass.entered((2, 1, 1.33));
+        assert_eq!(water.refraction_index(), 1.5);
+        assert_eq!(water.left(1).refraction_index(), 1.33);
+
+        // Leaving a medium the path isn't in changes nothing.
+        assert_eq!(water.left(3).refraction_index(), 1.5);
+        assert!(water.left(3).contains(1) && water.left(3).contains(2));
+    }
+
+    #[test]
+    fn media_past_the_limit_are_ignored() {
+        let mut media = Media::default();
+        for id in 0..MAX_MEDIA as ObjectId {
+            media = media.entered((id, 0, 1.5));
+        }
+        let full = media.entered((100, 0, 2.0));
+        assert!(!full.contains(100));
+        assert_eq!(full.refraction_index(), 1.5);
+        assert_eq!(full.left(100).len, MAX_MEDIA);
+
+        // So a dielectric entered then is passed straight through, leaving the media as is.
+        let diamond = Arc::new(Material::dielectric(2.0));
+        let r = Ray::new(Vec3::new(-1.0, 0.0, 1.0), Vec3::new(1.0, 0.0, -1.0));
+        let mut rec = hit_plane(&r, &diamond);
+        rec.object_id = 100;
+        let (sc_rec, after) = diamond
+            .scatter_through(&mut Rng::new(1), &r, &rec, &media)
+            .expect("scatter_through");
+        assert_eq!((sc_rec.scattered.dir - r.dir).length(), 0.0);
+        assert!(!after.contains(100));
+        assert_eq!(after.len, MAX_MEDIA);
+    }
+
+    #[test]
+    fn lower_priority_surfaces_are_passed_through() {
+        let glass = Arc::new(Material::dielectric(1.5).with_priority(2));
+        let water = Arc::new(Material::dielectric(1.33).with_priority(1));
+        let mut rng = Rng::new(1);
+
+        // Inside the glass, water's surface isn't there, though the path is in it from then on.
+        let in_glass = Media::default().entered((1, 2, 1.5));
+        let r = Ray::new(Vec3::new(-1.0, 0.0, 1.0), Vec3::new(1.0, 0.0, -1.0));
+        let mut rec = hit_plane(&r, &water);
+        rec.object_id = 2;
+        let (sc_rec, in_both) = water
+            .scatter_through(&mut rng, &r, &rec, &in_glass)
+            .expect("scatter_through");
+        let attenuation = sc_rec.attenuation;
+        assert_eq!(
+            [attenuation.r(), attenuation.g(), attenuation.b()],
+            [1.0; 3]
+        );
+        assert_eq!((sc_rec.scattered.dir - r.dir).length(), 0.0);
+        assert!(in_both.contains(2));
+        assert_eq!(in_both.refraction_index(), 1.5);
+
+        // Leaving the glass into the water bends light by their ratio, when it's refracted.
+        let r = Ray::new(Vec3::new(-1.0, 0.0, -1.0), Vec3::new(1.0, 0.0, 1.0));
+        let mut rec = hit_plane(&r, &glass);
+        rec.object_id = 1;
+        let sin_in = r.dir.unit().x();
+        for _ in 0..100 {
+            let (sc_rec, after) = glass
+                .scatter_through(&mut rng, &r, &rec, &in_both)
+                .expect("scatter_through");
+            let dir = sc_rec.scattered.dir.unit();
+            if dir.z() > 0.0 {
+                assert!((dir.x() - sin_in * 1.5 / 1.33).abs() < 1.0e-12);
+                assert!(!after.contains(1) && after.contains(2));
+                assert_eq!(after.refraction_index(), 1.33);
+            } else {
+                assert_eq!(after.refraction_index(), 1.5);
+            }
         }
     }
+
+    #[test]
+    fn one_sided_lights_are_black_from_behind() {
+        // Seen from in front, both kinds of light glow; from behind, only the two-sided one.
+        let emit = Color::new(4.0, 2.0, 1.0);
+        let two_sided = Arc::new(Material::diffuse_light(emit));
+        let one_sided = Arc::new(Material::diffuse_light(emit).one_sided());
+        let front = Ray::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let behind = Ray::new(Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let emitted = |r: &Ray, mat: &Arc<Material>| {
+            let rec = hit_plane(r, mat);
+            let c = mat.emitted(rec.u, rec.v, rec.p, rec.front_face);
+            [c.r(), c.g(), c.b()]
+        };
+
+        assert_eq!(emitted(&front, &two_sided), [4.0, 2.0, 1.0]);
+        assert_eq!(emitted(&behind, &two_sided), [4.0, 2.0, 1.0]);
+        assert_eq!(emitted(&front, &one_sided), [4.0, 2.0, 1.0]);
+        assert_eq!(emitted(&behind, &one_sided), [0.0, 0.0, 0.0]);
+        assert!(two_sided.is_two_sided() && !one_sided.is_two_sided());
+    }
+
+    #[test]
+    fn bump_ramps_tilt_normals_downhill() {
+        // Heights rising along `u` lean the normal back toward -u, and heights rising along `v`
+        // lean it toward -v, by the slope of the height times the scale.
+        let ramp = |height_at: fn(usize, usize) -> u8| {
+            let rgba: Vec<u8> = (0..64)
+                .flat_map(|i| {
+                    let h = height_at(i % 8, i / 8);
+                    [h, h, h, 255]
+                })
+                .collect();
+            Arc::new(ImageTexture::from_rgba8(
+                8,
+                8,
+                &rgba,
+                TextureFilter::Bilinear,
+            ))
+        };
+        let scale = 0.05;
+        let r = Ray::new(Vec3::new(0.5, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+
+        for (height, tilted_axis) in [
+            (ramp(|x, _| 20 * x as u8 + 40), 0),
+            (ramp(|_, y| 200 - 20 * y as u8), 1),
+        ] {
+            let clay = Material::lambertian(Color::new(0.5, 0.5, 0.5));
+            let mat = Arc::new(clay.with_bump(Arc::clone(&height), scale));
+            let rec = HitRecord {
+                u: 0.5,
+                v: 0.5,
+                ..hit_plane(&r, &mat)
+                    .with_derivatives(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0))
+            };
+            let (dhdu, dhdv) = height.slope(rec.u, rec.v);
+            assert!([dhdu, dhdv][tilted_axis] > 0.0 && [dhdu, dhdv][1 - tilted_axis] == 0.0);
+
+            let n = mat.shading_normal(&rec);
+            let expected = Vec3::new(-scale * dhdu, -scale * dhdv, 1.0).unit();
+            assert!((n - expected).near_zero());
+            assert!(<[f64; 3]>::from(n)[tilted_axis] < -0.01);
+        }
+    }
+
+    #[test]
+    fn checkered_lights_emit_their_texels() {
+        // A white and black checkerboard light glows in its white squares only, and gives off
+        // half its light on average.
+        let rgba = [
+            255, 255, 255, 255, 0, 0, 0, 255, //
+            0, 0, 0, 255, 255, 255, 255, 255,
+        ];
+        let checker = Arc::new(ImageTexture::from_rgba8(
+            2,
+            2,
+            &rgba,
+            TextureFilter::Nearest,
+        ));
+        let light = Material::textured_light(checker, Color::new(4.0, 2.0, 1.0));
+        let emitted = |u, v| {
+            let c = light.emitted(u, v, Vec3::new(0.0, 0.0, 0.0), true);
+            [c.r(), c.g(), c.b()]
+        };
+
+        assert_eq!(emitted(0.25, 0.75), [4.0, 2.0, 1.0]);
+        assert_eq!(emitted(0.75, 0.75), [0.0, 0.0, 0.0]);
+        assert_eq!(emitted(0.25, 0.25), [0.0, 0.0, 0.0]);
+        assert_eq!(emitted(0.75, 0.25), [4.0, 2.0, 1.0]);
+        let average = light.average_emitted();
+        assert_eq!([average.r(), average.g(), average.b()], [2.0, 1.0, 0.5]);
+    }
 }
@@ -0,0 +1,88 @@
+use crate::aabb::*;
+use crate::cone::*;
+use crate::cylinder::*;
+use crate::group::*;
+use crate::hit_record::*;
+use crate::quad::*;
+use crate::ray::*;
+use crate::sphere::*;
+use crate::triangle_mesh::*;
+
+pub enum Object {
+    Sphere(Sphere),
+    Cylinder(Cylinder),
+    Cone(Cone),
+    TriangleMesh(TriangleMesh),
+    Quad(Quad),
+    Group(Group),
+}
+
+impl Object {
+    pub fn bounding_box(&self) -> Aabb {
+        match self {
+            Self::Sphere(sphere) => sphere.bounding_box(),
+            Self::Cylinder(cylinder) => cylinder.bounding_box(),
+            Self::Cone(cone) => cone.bounding_box(),
+            Self::TriangleMesh(mesh) => mesh.bounding_box(),
+            Self::Quad(quad) => quad.bounding_box(),
+            Self::Group(group) => group.bounding_box(),
+        }
+    }
+
+    pub fn hit<'s>(&'s self, r: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord<'s>> {
+        match self {
+            Self::Sphere(sphere) => sphere.hit(r, ray_tmin, ray_tmax),
+            Self::Cylinder(cylinder) => cylinder.hit(r, ray_tmin, ray_tmax),
+            Self::Cone(cone) => cone.hit(r, ray_tmin, ray_tmax),
+            Self::TriangleMesh(mesh) => mesh.hit(r, ray_tmin, ray_tmax),
+            Self::Quad(quad) => quad.hit(r, ray_tmin, ray_tmax),
+            Self::Group(group) => group.hit(r, ray_tmin, ray_tmax),
+        }
+    }
+
+    pub fn hit4(&self, rays: &[Ray; 4], ray_tmin: f64, ray_tmax: [f64; 4]) -> [Option<f64>; 4] {
+        // Only spheres have a packet intersection; everything else is hit one ray at a time.
+        match self {
+            Self::Sphere(sphere) => sphere.hit4(rays, ray_tmin, ray_tmax),
+            _ => {
+                std::array::from_fn(|i| self.hit(&rays[i], ray_tmin, ray_tmax[i]).map(|rec| rec.t))
+            }
+        }
+    }
+}
+
+impl From<Sphere> for Object {
+    fn from(sphere: Sphere) -> Self {
+        Self::Sphere(sphere)
+    }
+}
+
+impl From<Cylinder> for Object {
+    fn from(cylinder: Cylinder) -> Self {
+        Self::Cylinder(cylinder)
+    }
+}
+
+impl From<Cone> for Object {
+    fn from(cone: Cone) -> Self {
+        Self::Cone(cone)
+    }
+}
+
+impl From<TriangleMesh> for Object {
+    fn from(mesh: TriangleMesh) -> Self {
+        Self::TriangleMesh(mesh)
+    }
+}
+
+impl From<Quad> for Object {
+    fn from(quad: Quad) -> Self {
+        Self::Quad(quad)
+    }
+}
+
+impl From<Group> for Object {
+    fn from(group: Group) -> Self {
+        Self::Group(group)
+    }
+}
@@ -0,0 +1,76 @@
+use std::io::{self, Write};
+
+fn crc32(bytes: &[&[u8]]) -> u32 {
+    let mut crc = 0xffff_ffff_u32;
+    for byte in bytes.iter().flat_map(|b| b.iter()) {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let mut a = 1_u32;
+    let mut b = 0_u32;
+    for chunk in bytes.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+fn write_chunk<W: Write>(w: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    w.write_all(&crc32(&[kind, data]).to_be_bytes())
+}
+
+// Writes an 8-bit RGBA image as a PNG, using uncompressed (stored) deflate blocks so that
+// no compression library is needed.
+pub fn write_rgba8<W: Write>(mut w: W, width: u32, height: u32, rgba: &[u8]) -> io::Result<()> {
+    assert_eq!(rgba.len(), 4 * width as usize * height as usize);
+
+    w.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut ihdr = vec![];
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    ihdr.extend_from_slice(&[8, 6, 0, 0, 0]); // 8-bit depth, RGBA, no interlace
+    write_chunk(&mut w, b"IHDR", &ihdr)?;
+
+    // Each scanline is preceded by its filter type, which is always zero (none) here.
+    let mut raw = Vec::with_capacity(rgba.len() + height as usize);
+    for row in rgba.chunks_exact(4 * width as usize) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut idat = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        idat.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let len = block.len() as u16;
+        idat.push(if blocks.peek().is_none() { 1 } else { 0 });
+        idat.extend_from_slice(&len.to_le_bytes());
+        idat.extend_from_slice(&(!len).to_le_bytes());
+        idat.extend_from_slice(block);
+    }
+    idat.extend_from_slice(&adler32(&raw).to_be_bytes());
+    write_chunk(&mut w, b"IDAT", &idat)?;
+
+    write_chunk(&mut w, b"IEND", &[])?;
+    w.flush()
+}
@@ -0,0 +1,66 @@
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+pub struct RenderPool {
+    jobs_tx: Option<Sender<Job>>, // Taken when dropped, to hang up on the worker threads
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl RenderPool {
+    pub fn new(num_threads: u8) -> Self {
+        // Worker threads that can be shared by cameras made with `Camera::with_pool`, each
+        // rendering whichever view was requested first and not yet taken by another.
+        assert!(num_threads > 0);
+
+        let (jobs_tx, jobs_rx) = std::sync::mpsc::channel::<Job>();
+        let jobs_rx: Arc<Mutex<Receiver<Job>>> = Arc::new(Mutex::new(jobs_rx));
+
+        let threads = (0..num_threads)
+            .map(|_| {
+                let jobs_rx = Arc::clone(&jobs_rx);
+                std::thread::spawn(move || {
+                    loop {
+                        // Only hold the lock while waiting, so others can take the next job.
+                        let job = jobs_rx.lock().expect("jobs_rx mutex").recv();
+                        let Ok(job) = job else {
+                            break;
+                        };
+                        job();
+                    }
+                })
+            })
+            .collect();
+
+        Self {
+            jobs_tx: Some(jobs_tx),
+            threads,
+        }
+    }
+
+    pub fn num_threads(&self) -> usize {
+        self.threads.len()
+    }
+
+    pub(crate) fn spawn<F: FnOnce() + Send + 'static>(&self, job: F) {
+        self.jobs_tx
+            .as_ref()
+            .expect("jobs_tx")
+            .send(Box::new(job))
+            .expect("jobs_tx");
+    }
+}
+
+impl Drop for RenderPool {
+    fn drop(&mut self) {
+        // Hanging up makes each worker's `recv` fail once the jobs left are done.
+        self.jobs_tx = None;
+
+        for thread in self.threads.drain(..) {
+            // A worker that panicked has already been reported; don't panic again here.
+            let _ = thread.join();
+        }
+    }
+}
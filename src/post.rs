@@ -0,0 +1,229 @@
+use crate::color::*;
+
+// A post-processing pass for `CameraOptions::post_effects`, with the parameters of its function.
+#[derive(Copy, Clone, Debug)]
+pub enum PostEffect {
+    Bloom { threshold: f64, radius: f64 },
+    Vignette { strength: f64 },
+    ChromaticAberration { amount: f64 },
+}
+
+impl PostEffect {
+    pub fn apply(&self, mut color: Vec<Color>, width: usize, height: usize) -> Vec<Color> {
+        match *self {
+            Self::Bloom { threshold, radius } => bloom(&color, width, height, threshold, radius),
+            Self::Vignette { strength } => {
+                vignette(&mut color, width, height, strength);
+                color
+            }
+            Self::ChromaticAberration { amount } => {
+                chromatic_aberration(&color, width, height, amount)
+            }
+        }
+    }
+}
+
+fn gaussian_kernel(radius: f64) -> Vec<f64> {
+    // Covers three standard deviations either side, normalized so blurring keeps total energy.
+    let half = radius.max(0.0).ceil() as usize;
+    let sigma = (radius / 3.0).max(1.0e-6);
+    let weights: Vec<f64> = (0..2 * half + 1)
+        .map(|i| {
+            let d = i as f64 - half as f64;
+            (-d * d / (2.0 * sigma * sigma)).exp()
+        })
+        .collect();
+    let total: f64 = weights.iter().sum();
+    weights.into_iter().map(|w| w / total).collect()
+}
+
+fn blur(
+    src: &[Color],
+    width: usize,
+    height: usize,
+    kernel: &[f64],
+    horizontal: bool,
+) -> Vec<Color> {
+    let half = (kernel.len() / 2) as isize;
+
+    (0..width * height)
+        .map(|i| {
+            let (x, y) = ((i % width) as isize, (i / width) as isize);
+            kernel
+                .iter()
+                .enumerate()
+                .map(|(k, weight)| {
+                    // Clamp to the nearest edge pixel when the kernel hangs off the image.
+                    let offset = k as isize - half;
+                    let (sx, sy) = if horizontal {
+                        ((x + offset).clamp(0, width as isize - 1), y)
+                    } else {
+                        (x, (y + offset).clamp(0, height as isize - 1))
+                    };
+                    *weight * src[sy as usize * width + sx as usize]
+                })
+                .sum()
+        })
+        .collect()
+}
+
+pub fn bloom(
+    color: &[Color],
+    width: usize,
+    height: usize,
+    threshold: f64,
+    radius: f64,
+) -> Vec<Color> {
+    assert_eq!(color.len(), width * height);
+
+    // Take the part of each pixel that's brighter than the threshold...
+    let bright: Vec<Color> = color
+        .iter()
+        .map(|c| {
+            let l = c.luminance();
+            if l > threshold {
+                ((l - threshold) / l) * *c
+            } else {
+                Color::new(0.0, 0.0, 0.0)
+            }
+        })
+        .collect();
+
+    // ... and spread it out with a separable Gaussian blur.
+    let kernel = gaussian_kernel(radius);
+    let blurred = blur(
+        &blur(&bright, width, height, &kernel, true),
+        width,
+        height,
+        &kernel,
+        false,
+    );
+
+    color
+        .iter()
+        .zip(&bright)
+        .zip(&blurred)
+        .map(|((c, b), g)| *c - *b + *g)
+        .collect()
+}
+
+pub fn vignette(color: &mut [Color], width: usize, height: usize, strength: f64) {
+    assert_eq!(color.len(), width * height);
+
+    // Darken by the squared distance from the center, normalized so that corners are at one, and
+    // scaled by `strength`, so zero changes nothing. The center is between pixels unless both
+    // dimensions are odd, so otherwise even the middle pixels are darkened, if only slightly.
+    let cx = (width as f64 - 1.0) / 2.0;
+    let cy = (height as f64 - 1.0) / 2.0;
+    let corner_dist_squared = (cx * cx + cy * cy).max(f64::MIN_POSITIVE);
+
+    for (i, c) in color.iter_mut().enumerate() {
+        let dx = (i % width) as f64 - cx;
+        let dy = (i / width) as f64 - cy;
+        let d_squared = (dx * dx + dy * dy) / corner_dist_squared;
+        *c = (1.0 - strength * d_squared).max(0.0) * *c;
+    }
+}
+
+pub fn chromatic_aberration(
+    color: &[Color],
+    width: usize,
+    height: usize,
+    amount: f64,
+) -> Vec<Color> {
+    assert_eq!(color.len(), width * height);
+
+    let cx = (width as f64 - 1.0) / 2.0;
+    let cy = (height as f64 - 1.0) / 2.0;
+
+    // Nearest pixel to (x, y) scaled away from the center, clamped to the image edges.
+    let sample = |x: f64, y: f64, scale: f64| {
+        let sx = (cx + (x - cx) * scale)
+            .round()
+            .clamp(0.0, width as f64 - 1.0) as usize;
+        let sy = (cy + (y - cy) * scale)
+            .round()
+            .clamp(0.0, height as f64 - 1.0) as usize;
+        color[sy * width + sx]
+    };
+
+    // Spread red outwards and blue inwards, leaving green where it is.
+    (0..width * height)
+        .map(|i| {
+            let (x, y) = ((i % width) as f64, (i / width) as f64);
+            Color::new(
+                sample(x, y, 1.0 + amount).r(),
+                color[i].g(),
+                sample(x, y, 1.0 - amount).b(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bloom_spreads_a_bright_pixel() {
+        let (width, height) = (9, 9);
+        let mut color = vec![Color::new(0.1, 0.1, 0.1); width * height];
+        color[4 * width + 4] = Color::new(10.0, 10.0, 10.0);
+        let bloomed = bloom(&color, width, height, 1.0, 2.0);
+
+        // Neighbors pick up some of the bright pixel, which loses some...
+        assert!(bloomed[4 * width + 4].luminance() < 10.0);
+        assert!(bloomed[4 * width + 5].luminance() > 0.1);
+        assert!(bloomed[3 * width + 4].luminance() > 0.1);
+        assert_eq!(bloomed[0].luminance(), 0.1);
+
+        // ... but the blur only moves light around.
+        let total = |color: &[Color]| color.iter().map(|c| c.luminance()).sum::<f64>();
+        assert!((total(&bloomed) - total(&color)).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn vignette_darkens_away_from_the_center() {
+        let gray = Color::new(0.5, 0.5, 0.5);
+        let darkened = |width: usize, height: usize, strength: f64| {
+            let mut color = vec![gray; width * height];
+            vignette(&mut color, width, height, strength);
+            color
+        };
+
+        // With odd dimensions the center pixel is at the very center, so it's unchanged.
+        let color = darkened(9, 5, 1.0);
+        assert_eq!(color[2 * 9 + 4].luminance(), gray.luminance());
+        assert_eq!(color[0].luminance(), 0.0);
+
+        // With even ones the middle pixels are just off center, so they're darkened a little.
+        let color = darkened(8, 4, 0.5);
+        let middle = color[2 * 8 + 4].luminance();
+        assert!(middle < gray.luminance() && middle > 0.95 * gray.luminance());
+        assert!(color[0].luminance() < middle);
+
+        assert!(
+            darkened(8, 4, 0.0)
+                .iter()
+                .all(|c| c.luminance() == gray.luminance())
+        );
+    }
+
+    #[test]
+    fn chromatic_aberration_of_zero_is_identity() {
+        let (width, height) = (6, 4);
+        let mut rng = crate::random::Rng::new(1);
+        let color: Vec<Color> = (0..width * height)
+            .map(|_| Color::new(rng.random_f64(), rng.random_f64(), rng.random_f64()))
+            .collect();
+        let channels = |c: &Color| [c.r(), c.g(), c.b()];
+
+        let same = chromatic_aberration(&color, width, height, 0.0);
+        assert!(same.iter().map(channels).eq(color.iter().map(channels)));
+
+        // Otherwise red and blue move, but green stays where it was.
+        let shifted = chromatic_aberration(&color, width, height, 0.5);
+        assert!(shifted.iter().zip(&color).all(|(a, b)| a.g() == b.g()));
+        assert!(!shifted.iter().map(channels).eq(color.iter().map(channels)));
+    }
+}
@@ -0,0 +1,148 @@
+use crate::aabb::*;
+use crate::hit_record::*;
+use crate::material::*;
+use crate::random::*;
+use crate::ray::*;
+use crate::vec3::*;
+
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct Quad {
+    q: Vec3,      // Starting corner
+    u: Vec3,      // Edge from `q` to one adjacent corner
+    v: Vec3,      // Edge from `q` to the other adjacent corner
+    w: Vec3,      // Cross product of `u` and `v` over its squared length, to find plane coordinates
+    normal: Vec3, // Unit normal, facing the side from which `u` turns anticlockwise to `v`
+    d: f64,       // Plane offset, such that `normal.dot(p) == d` for every point on the plane
+    area: f64,
+    mat: Arc<Material>,
+}
+
+impl Quad {
+    pub fn new(q: Vec3, u: Vec3, v: Vec3, mat: Arc<Material>) -> Self {
+        let n = u.cross(v);
+        let normal = n.unit();
+
+        Self {
+            q,
+            u,
+            v,
+            w: n / n.length_squared(),
+            normal,
+            d: normal.dot(q),
+            area: n.length(),
+            mat,
+        }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        self.q + 0.5 * (self.u + self.v)
+    }
+
+    pub fn area(&self) -> f64 {
+        self.area
+    }
+
+    pub fn normal(&self) -> Vec3 {
+        self.normal
+    }
+
+    pub fn material(&self) -> &Arc<Material> {
+        &self.mat
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        Aabb::new(self.q, self.q + self.u + self.v)
+            .union(Aabb::new(self.q + self.u, self.q + self.v))
+    }
+
+    pub fn hit<'s>(&'s self, r: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord<'s>> {
+        // No hit if the ray is parallel to the plane.
+        let denom = self.normal.dot(r.dir);
+        if denom.abs() < 1.0e-8 {
+            return None;
+        }
+
+        let t = (self.d - self.normal.dot(r.pos)) / denom;
+        if t <= ray_tmin || t >= ray_tmax {
+            return None;
+        }
+
+        // Find the hit point's coordinates along the edges, which are in [0,1] inside the quad.
+        let planar_hitpt_vector = r.at(t) - self.q;
+        let alpha = self.w.dot(planar_hitpt_vector.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar_hitpt_vector));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        Some(
+            HitRecord::new(r, t, self.normal, (alpha, beta), &self.mat)
+                .with_derivatives(self.u, self.v),
+        )
+    }
+
+    pub fn pdf_toward(&self, origin: Vec3, dir: Vec3) -> f64 {
+        // The density `sample` gives the direction `dir` from `origin`, or zero if it misses.
+        let Some(rec) = self.hit(&Ray::new(origin, dir), 0.0, f64::INFINITY) else {
+            return 0.0;
+        };
+        let to_point = rec.p - origin;
+        let dist_squared = to_point.length_squared();
+        let cos_theta = self.normal.dot(to_point).abs() / dist_squared.sqrt();
+        dist_squared / (cos_theta * self.area)
+    }
+
+    pub fn sample(&self, origin: Vec3, rng: &mut Rng) -> Option<(Vec3, f64)> {
+        // Returns a uniformly random point on the quad, along with the probability density of the
+        // direction to it from `origin` per solid angle. A patch of the quad covers less solid
+        // angle the further away and more edge-on it is, so the density is the area density
+        // `1 / area` scaled by `distance^2 / cos_theta`. From the quad's own plane, it covers
+        // none at all, and there's nothing to sample.
+        let point = self.q + rng.random_f64() * self.u + rng.random_f64() * self.v;
+        let to_point = point - origin;
+        let dist_squared = to_point.length_squared();
+        let cos_theta = self.normal.dot(to_point).abs() / dist_squared.sqrt();
+        if cos_theta == 0.0 || cos_theta.is_nan() {
+            return None;
+        }
+        Some((point, dist_squared / (cos_theta * self.area)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::*;
+
+    #[test]
+    fn samples_lie_on_the_quad_with_their_pdf() {
+        // Points are in the quad, and their density per solid angle is `distance^2 / cos_theta`
+        // over the area, matching `pdf_toward` in the direction of each.
+        let mat = Arc::new(Material::diffuse_light(Color::new(1.0, 1.0, 1.0)));
+        let quad = Quad::new(
+            Vec3::new(-1.0, 2.0, -1.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 3.0),
+            mat,
+        );
+        let origin = Vec3::new(0.5, 0.0, 0.25);
+        let mut rng = Rng::new(1);
+        for _ in 0..100 {
+            let (point, pdf) = quad.sample(origin, &mut rng).expect("sample");
+            assert!((point.y() - 2.0).abs() < 1e-12);
+            assert!((-1.0..=1.0).contains(&point.x()));
+            assert!((-1.0..=2.0).contains(&point.z()));
+
+            let to_point = point - origin;
+            let cos_theta = to_point.y() / to_point.length();
+            let expected = to_point.length_squared() / (cos_theta * 6.0);
+            assert!((pdf - expected).abs() < 1e-9 * expected);
+            assert!((quad.pdf_toward(origin, to_point) - expected).abs() < 1e-9 * expected);
+        }
+
+        // Nothing is sampled from a point in the quad's plane, where the density is infinite.
+        assert!(quad.sample(Vec3::new(5.0, 2.0, 0.0), &mut rng).is_none());
+    }
+}
@@ -7,6 +7,7 @@ fn splitmix64_next(x: &mut u64) -> u64 {
     z ^ (z >> 31)
 }
 
+#[derive(Clone)]
 pub struct Rng {
     state: [u64; 4],
 }
@@ -23,6 +24,27 @@ impl Rng {
         }
     }
 
+    pub fn fork(&self, stream_id: u64) -> Self {
+        // A new generator mixed from this one's state and `stream_id`, e.g. one per tile, without
+        // advancing this one. The same state and `stream_id` always give the same generator.
+        let mut x = stream_id;
+        Self {
+            state: self.state.map(|s| {
+                x ^= s;
+                splitmix64_next(&mut x)
+            }),
+        }
+    }
+
+    pub fn state(&self) -> [u64; 4] {
+        self.state
+    }
+
+    pub fn from_state(state: [u64; 4]) -> Self {
+        // Continues the exact sequence of the generator whose `state` was saved.
+        Self { state }
+    }
+
     // xoshiro256+ adapted from https://prng.di.unimi.it/xoshiro256plus.c
     fn xoshiro256p_next(&mut self) -> u64 {
         let result = self.state[0].overflowing_add(self.state[3]).0;
@@ -60,12 +82,118 @@ impl Rng {
         (m >> 64) as u64
     }
 
+    pub fn random_range_i64(&mut self, min: i64, max: i64) -> i64 {
+        // Returns a number from `min` up to but not including `max`.
+        assert!(min < max);
+        let span = max.wrapping_sub(min) as u64;
+        min.wrapping_add(self.random_u64(span) as i64)
+    }
+
+    pub fn random_index(&mut self, len: usize) -> usize {
+        // Returns a random index into a slice of length `len`, which mustn't be empty.
+        assert!(len > 0);
+        self.random_u64(len as u64) as usize
+    }
+
     pub fn random_f64(&mut self) -> f64 {
-        let limit = (1 << 53) - 1;
-        self.random_u64(limit - 1) as f64 / limit as f64
+        // Returns a number in [0,1) from the top 53 bits, which are the best ones of xoshiro256+,
+        // so every multiple of 2^-53 in that range is equally likely.
+        (self.xoshiro256p_next() >> 11) as f64 * (1.0 / (1_u64 << 53) as f64)
     }
 
     pub fn random_f64_range(&mut self, min: f64, max: f64) -> f64 {
         min + (max - min) * self.random_f64()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_f64_is_in_unit_interval() {
+        // The largest and smallest outputs of xoshiro256+ are the sums of the first and last
+        // state words, so states can be set up to give exactly those.
+        assert_eq!(Rng::from_state([0, 0, 0, 0]).random_f64(), 0.0);
+        let largest = Rng::from_state([u64::MAX, 0, 0, 0]).random_f64();
+        assert!(largest < 1.0);
+        assert_eq!(largest, 1.0 - 2.0_f64.powi(-53));
+
+        let mut rng = Rng::new(1);
+        for _ in 0..1_000_000 {
+            let x = rng.random_f64();
+            assert!((0.0..1.0).contains(&x));
+        }
+    }
+
+    #[test]
+    fn state_resumes_the_sequence() {
+        let mut rng = Rng::new(5);
+        for _ in 0..100 {
+            rng.random_f64();
+        }
+
+        let mut resumed = Rng::from_state(rng.state());
+        for _ in 0..1000 {
+            assert_eq!(resumed.random_u64(u64::MAX), rng.random_u64(u64::MAX));
+        }
+    }
+
+    #[test]
+    fn forks_are_reproducible_and_independent() {
+        let rng = Rng::new(9);
+        let draws = |mut rng: Rng| -> Vec<f64> { (0..10_000).map(|_| rng.random_f64()).collect() };
+
+        // The same stream ID gives the same sequence, without advancing the parent.
+        assert_eq!(draws(rng.fork(1)), draws(rng.fork(1)));
+        assert_eq!(draws(rng.clone()), draws(Rng::new(9)));
+
+        // Different stream IDs, even adjacent ones, give sequences uncorrelated with each other
+        // and with the parent's own.
+        let streams = [draws(rng.clone()), draws(rng.fork(0)), draws(rng.fork(1))];
+        for i in 0..streams.len() {
+            for j in i + 1..streams.len() {
+                let (a, b) = (&streams[i], &streams[j]);
+                assert!(a.iter().zip(b).all(|(x, y)| x != y));
+
+                // Uniform draws have a mean of 1/2 and a variance of 1/12.
+                let n = a.len() as f64;
+                let covariance = a
+                    .iter()
+                    .zip(b)
+                    .map(|(x, y)| (x - 0.5) * (y - 0.5))
+                    .sum::<f64>();
+                let correlation = covariance / n * 12.0;
+                assert!(correlation.abs() < 0.05);
+            }
+        }
+    }
+
+    #[test]
+    fn integer_ranges_are_bounded_and_uniform() {
+        let mut rng = Rng::new(4);
+        let n: i32 = 60_000;
+
+        let mut counts = [0; 6];
+        for _ in 0..n {
+            let x = rng.random_range_i64(-3, 3);
+            assert!((-3..3).contains(&x));
+            counts[(x + 3) as usize] += 1;
+        }
+        assert!(counts.iter().all(|&count| (count - n / 6).abs() < n / 60));
+
+        let mut counts = [0; 5];
+        for _ in 0..n {
+            counts[rng.random_index(5)] += 1;
+        }
+        assert!(counts.iter().all(|&count| (count - n / 5).abs() < n / 50));
+
+        // Single-element ranges only have the one value, and ranges as wide as an `i64` can hold
+        // don't overflow.
+        for _ in 0..1000 {
+            assert_eq!(rng.random_index(1), 0);
+            assert!(rng.random_range_i64(i64::MIN, i64::MAX) < i64::MAX);
+            assert_eq!(rng.random_range_i64(i64::MAX - 1, i64::MAX), i64::MAX - 1);
+        }
+    }
+}
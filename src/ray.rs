@@ -3,9 +3,27 @@ use crate::vec3::*;
 pub struct Ray {
     pub pos: Vec3,
     pub dir: Vec3,
+    pub time: f64, // When the ray was cast, for scenes that move; zero if they don't
+    pub channel: Option<usize>, // The only color channel carried, once dispersion has picked one
 }
 
 impl Ray {
+    #[inline]
+    pub fn new(pos: Vec3, dir: Vec3) -> Self {
+        Self::new_at_time(pos, dir, 0.0)
+    }
+
+    #[inline]
+    pub fn new_at_time(pos: Vec3, dir: Vec3, time: f64) -> Self {
+        Self {
+            pos,
+            dir,
+            time,
+            channel: None,
+        }
+    }
+
+    #[inline]
     pub fn at(&self, t: f64) -> Vec3 {
         self.pos + t * self.dir
     }
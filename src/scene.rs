@@ -1,31 +1,582 @@
+use crate::aabb::*;
+use crate::background::*;
+use crate::camera::*;
+use crate::color::*;
 use crate::hit_record::*;
+use crate::light::*;
+use crate::material::*;
+use crate::object::*;
+use crate::quad::*;
+use crate::random::*;
 use crate::ray::*;
 use crate::sphere::*;
+use crate::vec3::*;
+
+use std::sync::Arc;
+
+// Index of an object in the order it was added to its scene.
+pub type ObjectId = u32;
+
+// Builds a scene and the camera options to view it with, drawing on `rng` for anything random.
+pub type SceneFn = fn(&mut Rng) -> (Scene, CameraOptions);
+
+// The scenes that can be picked by name, e.g. with `--scene` on the command line.
+pub const BUILT_IN_SCENES: &[(&str, SceneFn)] =
+    &[("demo", random_spheres), ("cornell", |_| cornell_box())];
+
+pub fn built_in_scene(name: &str) -> Option<SceneFn> {
+    BUILT_IN_SCENES
+        .iter()
+        .find(|(scene_name, _)| *scene_name == name)
+        .map(|&(_, scene_fn)| scene_fn)
+}
 
 pub struct Scene {
-    spheres: Vec<Sphere>,
+    objects: Vec<Object>,
+    lights: Vec<Light>,
+    light_objects: Vec<(ObjectId, usize)>, // Objects added as lights, with their light's index
+    bbox: Aabb, // Bounds of every object, to skip rays that miss them all
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Scene {
     pub fn new() -> Self {
-        Scene { spheres: vec![] }
+        Scene {
+            objects: vec![],
+            lights: vec![],
+            light_objects: vec![],
+            bbox: Aabb::empty(),
+        }
+    }
+
+    pub fn add<O: Into<Object>>(&mut self, object: O) {
+        let object = object.into();
+        self.bbox = self.bbox.union(object.bounding_box());
+        self.objects.push(object);
+    }
+
+    pub fn add_all<O: Into<Object>>(&mut self, objects: impl IntoIterator<Item = O>) {
+        // Like calling `add` for each object, with room made for them all at once, e.g. for
+        // meshes split into many pieces. IDs are given in the same order.
+        let objects = objects.into_iter();
+        self.objects.reserve(objects.size_hint().0);
+        for object in objects {
+            self.add(object);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Object> {
+        self.objects.iter()
+    }
+
+    pub fn add_light<L: Into<Light>>(&mut self, light: L) {
+        // Lights with a surface are added as objects too, so they can be seen and hit.
+        let light = light.into();
+        let object: Option<Object> = match &light {
+            Light::Point(_) | Light::Spot(_) => None,
+            Light::Sphere(sphere) => Some(sphere.clone().into()),
+            Light::Quad(quad) => Some(quad.clone().into()),
+        };
+        if let Some(object) = object {
+            self.light_objects
+                .push((self.objects.len() as ObjectId, self.lights.len()));
+            self.add(object);
+        }
+        self.lights.push(light);
+    }
+
+    pub fn is_light(&self, id: ObjectId) -> bool {
+        self.object_light(id).is_some()
+    }
+
+    pub fn object_light(&self, id: ObjectId) -> Option<&Light> {
+        // The light that object `id` was added as, if it was added with `add_light`.
+        let i = self
+            .light_objects
+            .binary_search_by_key(&id, |&(object_id, _)| object_id)
+            .ok()?;
+        Some(&self.lights[self.light_objects[i].1])
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
     }
 
-    pub fn add(&mut self, sphere: Sphere) {
-        self.spheres.push(sphere);
+    pub fn bounding_box(&self) -> Aabb {
+        self.bbox
     }
 
     pub fn hit<'s>(&'s self, r: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord<'s>> {
+        self.hit_limited(r, ray_tmin, ray_tmax, None).0
+    }
+
+    pub fn hit_limited<'s>(
+        &'s self,
+        r: &Ray,
+        ray_tmin: f64,
+        ray_tmax: f64,
+        max_tests: Option<usize>,
+    ) -> (Option<HitRecord<'s>>, bool) {
+        // Like `hit`, but tests at most `max_tests` objects, then settles for the closest hit
+        // found so far, which may not be the closest one. Also returns whether it gave up early.
+        // The test against the bounds of the whole scene is free, and doesn't count toward it.
+        if !self.bbox.hit(r, ray_tmin, ray_tmax) {
+            return (None, false);
+        }
+
         let mut hit_rec: Option<HitRecord<'s>> = None;
         let mut closest_so_far = ray_tmax;
 
-        for sphere in &self.spheres {
-            if let Some(rec) = sphere.hit(r, ray_tmin, closest_so_far) {
+        for (id, object) in self.objects.iter().enumerate() {
+            if max_tests.is_some_and(|max_tests| id >= max_tests) {
+                return (hit_rec, true);
+            }
+            if let Some(rec) = object.hit(r, ray_tmin, closest_so_far) {
                 closest_so_far = rec.t;
-                hit_rec = Some(rec);
+                hit_rec = Some(HitRecord {
+                    object_id: id as ObjectId,
+                    ..rec
+                });
             }
         }
 
-        hit_rec
+        (hit_rec, false)
+    }
+
+    pub fn pick(&self, r: &Ray, ray_tmin: f64) -> Option<ObjectId> {
+        // The object closest along the ray past `ray_tmin`, e.g. to find what's under a pixel
+        // with `pixel_ray` and the camera's `ray_epsilon`.
+        self.hit(r, ray_tmin, f64::INFINITY)
+            .map(|rec| rec.object_id)
+    }
+
+    pub fn hit_object<'s>(
+        &'s self,
+        r: &Ray,
+        ray_tmin: f64,
+        ray_tmax: f64,
+    ) -> Option<(ObjectId, HitRecord<'s>)> {
+        // Like `hit`, along with which object was hit, e.g. to edit what was clicked on.
+        self.hit(r, ray_tmin, ray_tmax)
+            .map(|rec| (rec.object_id, rec))
+    }
+
+    pub fn hit4<'s>(
+        &'s self,
+        rays: &[Ray; 4],
+        ray_tmin: f64,
+        ray_tmax: f64,
+    ) -> [Option<HitRecord<'s>>; 4] {
+        // Closest hits for a packet of four rays, which is faster for rays that travel together.
+        // Only the closest object along each ray needs a full hit record, so remember which one
+        // it was and what `ray_tmax` it was found with, then hit it again to get the record.
+        // Rays that miss the scene's bounds get an empty range, so no object can be hit by them.
+        let in_bbox = rays
+            .each_ref()
+            .map(|r| self.bbox.hit(r, ray_tmin, ray_tmax));
+        if !in_bbox.contains(&true) {
+            return [None, None, None, None];
+        }
+
+        let mut closest_so_far = in_bbox.map(|hit| if hit { ray_tmax } else { ray_tmin });
+        let mut closest_objects: [Option<(usize, f64)>; 4] = [None; 4];
+
+        for (id, object) in self.objects.iter().enumerate() {
+            let ts = object.hit4(rays, ray_tmin, closest_so_far);
+            for (i, t) in ts.into_iter().enumerate() {
+                if let Some(t) = t {
+                    closest_objects[i] = Some((id, closest_so_far[i]));
+                    closest_so_far[i] = t;
+                }
+            }
+        }
+
+        std::array::from_fn(|i| {
+            let (id, tmax) = closest_objects[i]?;
+            let rec = self.objects[id].hit(&rays[i], ray_tmin, tmax)?;
+            Some(HitRecord {
+                object_id: id as ObjectId,
+                ..rec
+            })
+        })
+    }
+
+    pub fn hit_any(&self, r: &Ray, ray_tmin: f64, ray_tmax: f64) -> bool {
+        // Stops at the first hit found, for rays that don't need the closest one, e.g. shadows.
+        if !self.bbox.hit(r, ray_tmin, ray_tmax) {
+            return false;
+        }
+
+        self.objects
+            .iter()
+            .any(|object| object.hit(r, ray_tmin, ray_tmax).is_some())
+    }
+}
+
+fn rotated_box(size: Vec3, angle: f64, offset: Vec3, mat: &Arc<Material>) -> [Quad; 6] {
+    // Quads for the sides of a box from the origin to `size`, turned by `angle` degrees around
+    // the Y axis, then moved by `offset`.
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let rotate = |p: Vec3| Vec3::new(cos * p.x() + sin * p.z(), p.y(), -sin * p.x() + cos * p.z());
+    let side = |q: Vec3, u: Vec3, v: Vec3| {
+        Quad::new(rotate(q) + offset, rotate(u), rotate(v), Arc::clone(mat))
+    };
+
+    let dx = Vec3::new(size.x(), 0.0, 0.0);
+    let dy = Vec3::new(0.0, size.y(), 0.0);
+    let dz = Vec3::new(0.0, 0.0, size.z());
+    let origin = Vec3::new(0.0, 0.0, 0.0);
+
+    [
+        side(dz, dx, dy),       // front
+        side(dx + dz, -dz, dy), // right
+        side(dx, -dx, dy),      // back
+        side(origin, dz, dy),   // left
+        side(dy + dz, dx, -dz), // top
+        side(origin, dx, dz),   // bottom
+    ]
+}
+
+pub fn random_spheres(rng: &mut Rng) -> (Scene, CameraOptions) {
+    // The final scene of "Ray Tracing in One Weekend": three big spheres among a field of small
+    // ones with random materials, on a huge sphere for the ground.
+    let mut scene = Scene::new();
+
+    let ground_material = Arc::new(Material::lambertian(Color::new(0.5, 0.5, 0.5)));
+    scene.add(Sphere::new(
+        Vec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        ground_material,
+    ));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let center = Vec3::new(
+                a as f64 + 0.9 * rng.random_f64(),
+                0.2,
+                b as f64 + 0.9 * rng.random_f64(),
+            );
+
+            if (center - Vec3::new(4.0, 0.2, 0.0)).length() <= 0.9 {
+                continue;
+            }
+
+            let choose_mat = rng.random_f64();
+            let sphere_material: Arc<Material> = if choose_mat < 0.8 {
+                // diffuse
+                let albedo =
+                    Color::from_vec3(Vec3::random(rng)) * Color::from_vec3(Vec3::random(rng));
+                Arc::new(Material::lambertian(albedo))
+            } else if choose_mat < 0.95 {
+                // metal
+                let albedo = Color::from_vec3(Vec3::random_range(rng, 0.5, 1.0));
+                let fuzz = rng.random_f64_range(0.0, 0.5);
+                Arc::new(Material::metal(albedo, fuzz))
+            } else {
+                // glass
+                Arc::new(Material::dielectric(1.5))
+            };
+
+            scene.add(Sphere::new(center, 0.2, sphere_material));
+        }
+    }
+
+    let material1 = Arc::new(Material::dielectric(1.5));
+    scene.add(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, material1));
+
+    let material2 = Arc::new(Material::lambertian(Color::new(0.4, 0.2, 0.1)));
+    scene.add(Sphere::new(Vec3::new(-4.0, 1.0, 0.0), 1.0, material2));
+
+    let material3 = Arc::new(Material::metal(Color::new(0.7, 0.6, 0.5), 0.0));
+    scene.add(Sphere::new(Vec3::new(4.0, 1.0, 0.0), 1.0, material3));
+
+    let camera_options = CameraOptions {
+        aspect_ratio: 16.0 / 9.0,
+        image_width: 1200,
+        max_depth: 50,
+        ray_epsilon: 0.001,
+        max_tests: None,
+        samples_per_pass: 1,
+        pixel_filter: PixelFilter::Box,
+        pixel_order: PixelOrder::Scanline,
+        gamma: 2.0,
+        vfov: 20.0,
+        lookfrom: Vec3::new(13.0, 2.0, 3.0),
+        lookat: Vec3::new(0.0, 0.0, 0.0),
+        vup: Vec3::new(0.0, 1.0, 0.0),
+        defocus_angle: 0.6,
+        focus_dist: 10.0,
+        aperture_blades: 0,
+        background: Background::Gradient {
+            up: Vec3::new(0.0, 1.0, 0.0),
+        },
+        sky_intensity: 1.0,
+        alpha_background: false,
+        render_mode: RenderMode::Shaded,
+        bbox_overlay: false,
+        max_passes: Some(500),
+        override_material: None,
+        stereo: None,
+        post_effects: vec![],
+    };
+
+    (scene, camera_options)
+}
+
+pub fn cornell_box() -> (Scene, CameraOptions) {
+    // The Cornell box as in "Ray Tracing: The Next Week": a white room with a green wall on the
+    // left, a red wall on the right, and two white boxes, lit by nothing but a ceiling light.
+    let red = Arc::new(Material::lambertian(Color::new(0.65, 0.05, 0.05)));
+    let white = Arc::new(Material::lambertian(Color::new(0.73, 0.73, 0.73)));
+    let green = Arc::new(Material::lambertian(Color::new(0.12, 0.45, 0.15)));
+    let light = Arc::new(Material::diffuse_light(Color::new(15.0, 15.0, 15.0)));
+
+    let mut scene = Scene::new();
+
+    scene.add(Quad::new(
+        Vec3::new(555.0, 0.0, 0.0),
+        Vec3::new(0.0, 555.0, 0.0),
+        Vec3::new(0.0, 0.0, 555.0),
+        green,
+    ));
+    scene.add(Quad::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 555.0, 0.0),
+        Vec3::new(0.0, 0.0, 555.0),
+        red,
+    ));
+    scene.add_light(Quad::new(
+        Vec3::new(343.0, 554.0, 332.0),
+        Vec3::new(-130.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -105.0),
+        light,
+    ));
+    scene.add(Quad::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(555.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 555.0),
+        Arc::clone(&white),
+    ));
+    scene.add(Quad::new(
+        Vec3::new(555.0, 555.0, 555.0),
+        Vec3::new(-555.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -555.0),
+        Arc::clone(&white),
+    ));
+    scene.add(Quad::new(
+        Vec3::new(0.0, 0.0, 555.0),
+        Vec3::new(555.0, 0.0, 0.0),
+        Vec3::new(0.0, 555.0, 0.0),
+        Arc::clone(&white),
+    ));
+
+    let tall_box = rotated_box(
+        Vec3::new(165.0, 330.0, 165.0),
+        15.0,
+        Vec3::new(265.0, 0.0, 295.0),
+        &white,
+    );
+    let short_box = rotated_box(
+        Vec3::new(165.0, 165.0, 165.0),
+        -18.0,
+        Vec3::new(130.0, 0.0, 65.0),
+        &white,
+    );
+    for side in tall_box.into_iter().chain(short_box) {
+        scene.add(side);
+    }
+
+    let camera_options = CameraOptions {
+        aspect_ratio: 1.0,
+        image_width: 600,
+        max_depth: 50,
+        vfov: 40.0,
+        lookfrom: Vec3::new(278.0, 278.0, -800.0),
+        lookat: Vec3::new(278.0, 278.0, 0.0),
+        vup: Vec3::new(0.0, 1.0, 0.0),
+        defocus_angle: 0.0,
+        background: Background::Solid(Color::new(0.0, 0.0, 0.0)),
+        ..Default::default()
+    };
+
+    (scene, camera_options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::triangle_mesh::*;
+
+    #[test]
+    fn hit4_matches_hit() {
+        // Overlapping spheres and a quad, hit by packets of rays from all over, some of which
+        // miss the scene's bounds entirely.
+        let mut rng = Rng::new(3);
+        let mat = Arc::new(Material::lambertian(Color::new(0.5, 0.5, 0.5)));
+        let mut scene = Scene::new();
+        for _ in 0..20 {
+            let center = Vec3::random_range(&mut rng, -2.0, 2.0);
+            let radius = rng.random_f64_range(0.1, 0.8);
+            scene.add(Sphere::new(center, radius, Arc::clone(&mat)));
+        }
+        scene.add(Quad::new(
+            Vec3::new(-2.0, -1.0, -2.0),
+            Vec3::new(4.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 4.0),
+            Arc::clone(&mat),
+        ));
+
+        let mut hits = 0;
+        for _ in 0..10_000 {
+            let rays: [Ray; 4] = std::array::from_fn(|_| {
+                let pos = Vec3::random_range(&mut rng, -4.0, 4.0);
+                Ray::new(pos, Vec3::random_unit_vector(&mut rng))
+            });
+            let packet = scene.hit4(&rays, 0.001, f64::INFINITY);
+            for (r, packet_rec) in rays.iter().zip(packet) {
+                let key = |rec: Option<HitRecord>| rec.map(|rec| (rec.t, rec.object_id));
+                let hit = key(scene.hit(r, 0.001, f64::INFINITY));
+                assert_eq!(key(packet_rec), hit);
+                hits += hit.is_some() as usize;
+            }
+        }
+        // Plenty of rays should both hit and miss for the comparison to mean anything.
+        assert!(1_000 < hits && hits < 39_000);
+    }
+
+    #[test]
+    fn built_in_scenes_can_all_be_built() {
+        // Each scene is found by its name, has something in it, and can be rendered.
+        for &(name, scene_fn) in BUILT_IN_SCENES {
+            assert!(built_in_scene(name).is_some());
+            assert_eq!(
+                BUILT_IN_SCENES.iter().filter(|(n, _)| *n == name).count(),
+                1
+            );
+
+            let (scene, options) = scene_fn(&mut Rng::new(0));
+            assert!(!scene.is_empty());
+            let options = CameraOptions {
+                image_width: 8,
+                max_depth: 4,
+                ..options
+            };
+            let mut camera = Camera::new(&Arc::new(scene), 7, 1, options);
+            camera.render_to_completion(1);
+            assert!(camera.frame_rgba8().iter().any(|&c| c != 0));
+        }
+        assert!(built_in_scene("nonexistent").is_none());
+    }
+
+    #[test]
+    fn add_all_matches_adding_one_at_a_time() {
+        // A thousand small triangles, each its own object, in the same order either way.
+        let mut rng = Rng::new(5);
+        let mat = Arc::new(Material::lambertian(Color::new(0.5, 0.5, 0.5)));
+        let positions: Vec<Vec3> = (0..1000)
+            .flat_map(|_| {
+                let p = Vec3::random_range(&mut rng, -2.0, 2.0);
+                let corners: [Vec3; 3] =
+                    std::array::from_fn(|_| p + Vec3::random_range(&mut rng, -0.3, 0.3));
+                corners
+            })
+            .collect();
+        let positions = Arc::new(positions);
+        let triangles = || {
+            (0..1000).map(|i| {
+                let tri = [3 * i, 3 * i + 1, 3 * i + 2];
+                TriangleMesh::new(Arc::clone(&positions), vec![tri], Arc::clone(&mat))
+            })
+        };
+
+        let mut one_at_a_time = Scene::new();
+        for triangle in triangles() {
+            one_at_a_time.add(triangle);
+        }
+        let mut all_at_once = Scene::new();
+        all_at_once.add_all(triangles());
+        assert_eq!(all_at_once.len(), 1000);
+
+        let mut hits = 0;
+        for _ in 0..1000 {
+            let r = Ray::new(
+                Vec3::random_range(&mut rng, -3.0, 3.0),
+                Vec3::random_unit_vector(&mut rng),
+            );
+            let key = |rec: Option<HitRecord>| rec.map(|rec| (rec.t, rec.object_id));
+            let hit = key(one_at_a_time.hit(&r, 0.001, f64::INFINITY));
+            assert_eq!(key(all_at_once.hit(&r, 0.001, f64::INFINITY)), hit);
+            hits += hit.is_some() as usize;
+        }
+        assert!(hits > 100);
+    }
+
+    #[test]
+    fn hit_object_names_the_object_hit() {
+        // Spheres in a row along X, with a light added among them, are numbered in the order
+        // they were added; rays straight down onto each find that one.
+        let mat = Arc::new(Material::lambertian(Color::new(0.5, 0.5, 0.5)));
+        let light = Arc::new(Material::diffuse_light(Color::new(4.0, 4.0, 4.0)));
+        let mut scene = Scene::new();
+        for x in 0..5 {
+            let center = Vec3::new(x as f64, 0.0, 0.0);
+            if x == 2 {
+                scene.add_light(Sphere::new(center, 0.4, Arc::clone(&light)));
+            } else {
+                scene.add(Sphere::new(center, 0.4, Arc::clone(&mat)));
+            }
+        }
+
+        for x in 0..5 {
+            let r = Ray::new(Vec3::new(x as f64, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+            let (id, rec) = scene
+                .hit_object(&r, 0.001, f64::INFINITY)
+                .expect("hit_object");
+            assert_eq!(id, x);
+            assert_eq!(rec.object_id, x);
+            assert!((rec.p - Vec3::new(x as f64, 0.4, 0.0)).near_zero());
+            assert_eq!(scene.is_light(id), x == 2);
+        }
+        let r = Ray::new(Vec3::new(0.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(scene.hit_object(&r, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn hit_limited_stops_after_max_tests() {
+        // Ten spheres along the Z axis, added furthest first, so each one tested is closer.
+        let mat = Arc::new(Material::lambertian(Color::new(0.5, 0.5, 0.5)));
+        let mut scene = Scene::new();
+        for i in 0..10 {
+            let center = Vec3::new(0.0, 0.0, -20.0 + 2.0 * i as f64);
+            scene.add(Sphere::new(center, 0.5, Arc::clone(&mat)));
+        }
+        let r = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit = |max_tests: Option<usize>| {
+            let (rec, stopped) = scene.hit_limited(&r, 0.001, f64::INFINITY, max_tests);
+            (rec.map(|rec| rec.object_id), stopped)
+        };
+
+        assert_eq!(hit(None), (Some(9), false));
+        assert_eq!(hit(Some(10)), (Some(9), false));
+        assert_eq!(hit(Some(3)), (Some(2), true));
+        assert_eq!(hit(Some(0)), (None, true));
+
+        // Missing the scene's bounds takes no tests at all.
+        let miss = Ray::new(Vec3::new(5.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let (rec, stopped) = scene.hit_limited(&miss, 0.001, f64::INFINITY, Some(0));
+        assert!(rec.is_none() && !stopped);
     }
 }
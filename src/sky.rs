@@ -0,0 +1,232 @@
+use crate::color::*;
+use crate::random::*;
+use crate::vec3::*;
+
+use std::f64::consts::PI;
+
+// Preetham, Shirley and Smits, "A Practical Analytic Model for Daylight" (1999), with +Y up.
+// Luminance is in thousands of cd/m^2, so scale it to taste with the camera's `sky_intensity`.
+#[derive(Clone, Debug)]
+pub struct Sky {
+    sun_dir: Vec3,
+    perez: [[f64; 5]; 3], // Distribution coefficients A to E for Y, x and y
+    zenith: [f64; 3],     // Luminance Y and chromaticity x, y straight up
+    sun_cos_radius: f64,  // Cosine of the sun disk's angular radius
+    sun_intensity: f64,   // Sun disk radiance as a multiple of the sky right next to it
+}
+
+fn perez_distribution(theta: f64, gamma: f64, [a, b, c, d, e]: [f64; 5]) -> f64 {
+    // Relative brightness of the sky at `theta` from the zenith and `gamma` from the sun.
+    (1.0 + a * (b / theta.cos()).exp()) * (1.0 + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+}
+
+impl Sky {
+    pub fn new(sun_dir: Vec3, turbidity: f64) -> Self {
+        // A clear sky lit by the sun toward `sun_dir`, hazier with `turbidity` from about 2 for
+        // very clear air to 10 for haze. The sun disk is its real size, half a degree across.
+        let t = turbidity;
+        let sun_dir = sun_dir.unit();
+        let theta_s = sun_dir.y().clamp(-1.0, 1.0).acos().min(0.5 * PI);
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_y = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let chromaticity = |k2: [f64; 3], k1: [f64; 4], k0: [f64; 4]| {
+            let powers = [theta_s.powi(3), theta_s.powi(2), theta_s, 1.0];
+            let dot = |k: &[f64]| k.iter().zip(powers).map(|(k, p)| k * p).sum::<f64>();
+            t * t * dot(&k2) + t * dot(&k1) + dot(&k0)
+        };
+        let zenith_x = chromaticity(
+            [0.00166, -0.00375, 0.00209],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        );
+        let zenith_chroma_y = chromaticity(
+            [0.00275, -0.00610, 0.00317],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        );
+
+        // Each coefficient set is normalized by its value at the zenith, which is `theta_s`
+        // from the sun, so it gives the zenith values there.
+        let zenith = [zenith_y, zenith_x, zenith_chroma_y];
+        let zenith =
+            std::array::from_fn(|i| zenith[i] / perez_distribution(0.0, theta_s, perez[i]));
+
+        Self {
+            sun_dir,
+            perez,
+            zenith,
+            sun_cos_radius: 0.00465_f64.cos(),
+            sun_intensity: 50.0,
+        }
+    }
+
+    pub fn with_sun_disk(self, angular_radius: f64, intensity: f64) -> Self {
+        // Sets the sun disk's angular radius in radians, and its radiance as a multiple of the
+        // sky right next to it. The real sun is far brighter than the default, but would make
+        // renders too noisy when only found by chance; an intensity of zero hides it.
+        Self {
+            sun_cos_radius: angular_radius.cos(),
+            sun_intensity: intensity,
+            ..self
+        }
+    }
+
+    pub fn sun_dir(&self) -> Vec3 {
+        self.sun_dir
+    }
+
+    pub fn has_sun_disk(&self) -> bool {
+        self.sun_intensity > 0.0 && self.sun_cos_radius < 1.0
+    }
+
+    pub fn sample_sun(&self, _origin: Vec3, rng: &mut Rng) -> (Vec3, f64, Color) {
+        // A random unit direction within the sun disk, uniformly over its solid angle, along
+        // with its probability density and the radiance from it. The sun is infinitely far
+        // away, so this is the same from any `_origin`.
+        let cos_theta = 1.0 - rng.random_f64() * (1.0 - self.sun_cos_radius);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * rng.random_f64();
+
+        // Build an orthonormal basis around the sun's direction.
+        let w = self.sun_dir;
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(a).unit();
+        let u = w.cross(v);
+
+        let dir = sin_theta * phi.cos() * u + sin_theta * phi.sin() * v + cos_theta * w;
+        (dir, self.sun_pdf(dir), self.color(dir))
+    }
+
+    pub fn sun_pdf(&self, dir: Vec3) -> f64 {
+        // The probability density per solid angle of `sample_sun` picking the direction `dir`,
+        // which is zero outside the sun disk.
+        if self.has_sun_disk() && dir.unit().dot(self.sun_dir) >= self.sun_cos_radius {
+            1.0 / (2.0 * PI * (1.0 - self.sun_cos_radius))
+        } else {
+            0.0
+        }
+    }
+
+    pub fn color(&self, dir: Vec3) -> Color {
+        // Directions below the horizon see the sky at the horizon, where the model stops.
+        let dir = dir.unit();
+        let theta = dir.y().clamp(-1.0, 1.0).acos().min(0.5 * PI - 1.0e-3);
+        let cos_gamma = dir.dot(self.sun_dir).clamp(-1.0, 1.0);
+        let gamma = cos_gamma.acos();
+
+        let [big_y, x, y] = std::array::from_fn(|i| {
+            self.zenith[i] * perez_distribution(theta, gamma, self.perez[i])
+        });
+        let sky = xyy_to_rgb(x, y, big_y);
+
+        if cos_gamma >= self.sun_cos_radius && dir.y() >= 0.0 {
+            self.sun_intensity * sky
+        } else {
+            sky
+        }
+    }
+}
+
+fn xyy_to_rgb(x: f64, y: f64, big_y: f64) -> Color {
+    // CIE xyY to linear sRGB primaries with a D65 white point, leaving out colors outside them.
+    let big_x = x / y * big_y;
+    let big_z = (1.0 - x - y) / y * big_y;
+    Color::new(
+        (3.2406 * big_x - 1.5372 * big_y - 0.4986 * big_z).max(0.0),
+        (-0.9689 * big_x + 1.8758 * big_y + 0.0415 * big_z).max(0.0),
+        (0.0557 * big_x - 0.2040 * big_y + 1.0570 * big_z).max(0.0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sun_samples_fall_in_the_disk() {
+        // Every sample is a unit direction within the disk's angular radius, with a density of
+        // one over the disk's solid angle, and sees the disk's radiance.
+        let radius: f64 = 0.05;
+        let sky = Sky::new(Vec3::new(1.0, 1.0, 0.5), 3.0).with_sun_disk(radius, 10.0);
+        let solid_angle = 2.0 * PI * (1.0 - radius.cos());
+        let beside = sky.sun_dir() + 3.0 * radius * sky.sun_dir().cross(Vec3::new(0.0, 1.0, 0.0));
+        let mut rng = Rng::new(1);
+        let mut furthest: f64 = 0.0;
+        for _ in 0..1000 {
+            let (dir, pdf, radiance) = sky.sample_sun(Vec3::new(0.0, 0.0, 0.0), &mut rng);
+            assert!((dir.length() - 1.0).abs() < 1e-12);
+            let angle = dir.dot(sky.sun_dir()).min(1.0).acos();
+            assert!(angle <= radius + 1e-9);
+            furthest = furthest.max(angle);
+            assert!((pdf - 1.0 / solid_angle).abs() < 1e-9 / solid_angle);
+            assert_eq!(pdf, sky.sun_pdf(dir));
+            assert!(radiance.luminance() > 5.0 * sky.color(beside).luminance());
+        }
+        assert!(furthest > 0.9 * radius);
+
+        // Outside the disk, or without one, nothing would have been sampled.
+        assert_eq!(sky.sun_pdf(Vec3::new(0.0, 1.0, 0.0)), 0.0);
+        let no_disk = sky.with_sun_disk(radius, 0.0);
+        assert_eq!(no_disk.sun_pdf(no_disk.sun_dir()), 0.0);
+    }
+
+    #[test]
+    fn sky_radiance_is_plausible() {
+        // Straight up the sky has its zenith luminance and is bluer than it is red; it brightens
+        // toward the sun and the horizon, and the sun disk outshines it all.
+        let sun_dir = Vec3::new(0.0, 0.5, -1.0);
+        let sky = Sky::new(sun_dir, 2.5);
+        let theta_s = sun_dir.unit().y().acos();
+        let chi = (4.0 / 9.0 - 2.5 / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_y = (4.0453 * 2.5 - 4.9710) * chi.tan() - 0.2155 * 2.5 + 2.4192;
+        let zenith = sky.color(Vec3::new(0.0, 1.0, 0.0));
+        assert!((zenith.luminance() - zenith_y).abs() < 0.05 * zenith_y);
+        assert!(zenith.b() > zenith.r());
+
+        let toward_sun = sky.color(Vec3::new(0.0, 0.5, -0.8));
+        let away_from_sun = sky.color(Vec3::new(0.0, 0.5, 0.8));
+        assert!(toward_sun.luminance() > 2.0 * away_from_sun.luminance());
+        assert!(sky.color(Vec3::new(1.0, 0.1, 0.0)).luminance() > zenith.luminance());
+        assert!(sky.color(sun_dir).luminance() > 10.0 * toward_sun.luminance());
+
+        // Nowhere, above the horizon or below it, is it negative or NaN.
+        for i in 0..100 {
+            let (theta, phi) = (PI * (i as f64 + 0.5) / 100.0, 0.7 * i as f64);
+            let c = sky.color(Vec3::from_spherical(theta, phi));
+            assert!(
+                [c.r(), c.g(), c.b()]
+                    .iter()
+                    .all(|&x| x >= 0.0 && x.is_finite())
+            );
+        }
+    }
+}
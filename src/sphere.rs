@@ -1,13 +1,18 @@
+use crate::aabb::*;
 use crate::hit_record::*;
 use crate::material::*;
+use crate::random::*;
 use crate::ray::*;
 use crate::vec3::*;
 
 use std::sync::Arc;
 
+#[derive(Clone)]
 pub struct Sphere {
     center: Vec3,
     radius: f64,
+    radius_squared: f64,
+    inv_radius: f64,
     mat: Arc<Material>,
 }
 
@@ -16,15 +21,35 @@ impl Sphere {
         Self {
             center,
             radius,
+            radius_squared: radius * radius,
+            inv_radius: 1.0 / radius,
             mat,
         }
     }
 
+    pub fn center(&self) -> Vec3 {
+        self.center
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    pub fn material(&self) -> &Arc<Material> {
+        &self.mat
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Aabb::new(self.center - r, self.center + r)
+    }
+
+    #[inline]
     pub fn hit<'s>(&'s self, r: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord<'s>> {
         let oc = self.center - r.pos;
         let a = r.dir.length_squared();
         let h = r.dir.dot(oc);
-        let c = oc.length_squared() - self.radius * self.radius;
+        let c = oc.length_squared() - self.radius_squared;
 
         let discriminant = h * h - a * c;
         if discriminant < 0.0 {
@@ -33,20 +58,172 @@ impl Sphere {
 
         let sqrtd = discriminant.sqrt();
 
-        // Find the nearest root that lies in the acceptable range.
-        let mut root = (h - sqrtd) / a;
-        if root <= ray_tmin || root >= ray_tmax {
-            root = (h + sqrtd) / a;
-            if root <= ray_tmin || root >= ray_tmax {
+        let root = Self::nearest_root((h - sqrtd) / a, (h + sqrtd) / a, ray_tmin, ray_tmax)?;
+
+        let outward_normal = self.inv_radius * (r.at(root) - self.center);
+        let (dpdu, dpdv) = self.get_derivatives(outward_normal);
+
+        Some(
+            HitRecord::new(
+                r,
+                root,
+                outward_normal,
+                Self::get_uv(outward_normal),
+                &self.mat,
+            )
+            .with_derivatives(dpdu, dpdv),
+        )
+    }
+
+    pub fn hit4(&self, rays: &[Ray; 4], ray_tmin: f64, ray_tmax: [f64; 4]) -> [Option<f64>; 4] {
+        // Like `hit` but only finds `t` of each hit, solving the quadratic for four rays at once
+        // so it can be vectorized.
+        let oc: [Vec3; 4] = std::array::from_fn(|i| self.center - rays[i].pos);
+        let a: [f64; 4] = std::array::from_fn(|i| rays[i].dir.length_squared());
+        let h: [f64; 4] = std::array::from_fn(|i| rays[i].dir.dot(oc[i]));
+        let c: [f64; 4] = std::array::from_fn(|i| oc[i].length_squared() - self.radius_squared);
+
+        let discriminant: [f64; 4] = std::array::from_fn(|i| h[i] * h[i] - a[i] * c[i]);
+        let sqrtd = discriminant.map(|d| d.max(0.0).sqrt());
+
+        std::array::from_fn(|i| {
+            if discriminant[i] < 0.0 {
                 return None;
             }
+            let near = (h[i] - sqrtd[i]) / a[i];
+            let far = (h[i] + sqrtd[i]) / a[i];
+            Self::nearest_root(near, far, ray_tmin, ray_tmax[i])
+        })
+    }
+
+    #[inline]
+    fn nearest_root(near: f64, far: f64, ray_tmin: f64, ray_tmax: f64) -> Option<f64> {
+        // Find the nearest root that lies in the acceptable range.
+        if near > ray_tmin && near < ray_tmax {
+            Some(near)
+        } else if far > ray_tmin && far < ray_tmax {
+            Some(far)
+        } else {
+            None
         }
+    }
 
-        Some(HitRecord::new(
-            r,
-            root,
-            (r.at(root) - self.center) / self.radius,
-            &self.mat,
-        ))
+    pub fn pdf_toward(&self, origin: Vec3, dir: Vec3) -> f64 {
+        // The density `sample_toward` gives the direction `dir` from `origin`.
+        let to_center = self.center - origin;
+        let dist_squared = to_center.length_squared();
+        if dist_squared <= self.radius_squared {
+            return 1.0 / (4.0 * std::f64::consts::PI);
+        }
+
+        let cos_theta_max = (1.0 - self.radius_squared / dist_squared).sqrt();
+        if dir.unit().dot(to_center) < cos_theta_max * dist_squared.sqrt() {
+            return 0.0;
+        }
+        1.0 / (2.0 * std::f64::consts::PI * (1.0 - cos_theta_max))
+    }
+
+    pub fn sample_toward(&self, origin: Vec3, rng: &mut Rng) -> (Vec3, f64) {
+        // Returns a random unit direction from `origin` toward the sphere, uniformly within the
+        // cone of directions it subtends, along with the solid angle probability density of it.
+        let to_center = self.center - origin;
+        let dist_squared = to_center.length_squared();
+
+        // From inside the sphere, every direction hits it.
+        if dist_squared <= self.radius_squared {
+            let theta = (1.0 - 2.0 * rng.random_f64()).acos();
+            let phi = 2.0 * std::f64::consts::PI * rng.random_f64();
+            return (
+                Vec3::from_spherical(theta, phi),
+                1.0 / (4.0 * std::f64::consts::PI),
+            );
+        }
+
+        let cos_theta_max = (1.0 - self.radius_squared / dist_squared).sqrt();
+        let cos_theta = 1.0 - rng.random_f64() * (1.0 - cos_theta_max);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * std::f64::consts::PI * rng.random_f64();
+
+        // Build an orthonormal basis around the direction to the sphere's center.
+        let w = to_center / dist_squared.sqrt();
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(a).unit();
+        let u = w.cross(v);
+
+        let dir = sin_theta * phi.cos() * u + sin_theta * phi.sin() * v + cos_theta * w;
+        let pdf = 1.0 / (2.0 * std::f64::consts::PI * (1.0 - cos_theta_max));
+        (dir, pdf)
+    }
+
+    #[inline]
+    fn get_derivatives(&self, p: Vec3) -> (Vec3, Vec3) {
+        // The rates of change of the hit point with `u` and `v` from `get_uv`, at the point `p`
+        // on the sphere of radius one; `u` turns around the Y axis and `v` climbs toward +Y.
+        // Both are left at zero at the poles, where `u` is undefined.
+        let (x, y, z) = (p.x(), p.y(), p.z());
+        let sin_theta = (x * x + z * z).sqrt();
+        if sin_theta < 1.0e-8 {
+            return (Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0));
+        }
+        let dpdu = (2.0 * std::f64::consts::PI * self.radius) * Vec3::new(z, 0.0, -x);
+        let dpdv = (std::f64::consts::PI * self.radius)
+            * Vec3::new(-x * y / sin_theta, sin_theta, -y * z / sin_theta);
+        (dpdu, dpdv)
+    }
+
+    #[inline]
+    fn get_uv(p: Vec3) -> (f64, f64) {
+        // p: a given point on the sphere of radius one, centered at the origin.
+        // u: returned value [0,1] of angle around the Y axis from X=-1.
+        // v: returned value [0,1] of angle from Y=-1 to Y=+1.
+        //     <1 0 0> yields <0.50 0.50>       <-1  0  0> yields <0.00 0.50>
+        //     <0 1 0> yields <0.50 1.00>       < 0 -1  0> yields <0.50 0.00>
+        //     <0 0 1> yields <0.25 0.50>       < 0  0 -1> yields <0.75 0.50>
+
+        // Hit points are found with rounding, so `p.y()` can be a hair past -1 or +1 at a pole.
+        let theta = (-p.y()).clamp(-1.0, 1.0).acos();
+        let phi = (-p.z()).atan2(p.x()) + std::f64::consts::PI;
+
+        (
+            phi / (2.0 * std::f64::consts::PI),
+            theta / std::f64::consts::PI,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::*;
+
+    #[test]
+    fn uvs_are_defined_at_the_poles() {
+        // The corners of the mapping in `get_uv`, then rays straight onto the poles of spheres
+        // that aren't at the origin, which land on them up to rounding.
+        let uv = |x: f64, y: f64, z: f64| Sphere::get_uv(Vec3::new(x, y, z));
+        assert_eq!(uv(1.0, 0.0, 0.0), (0.5, 0.5));
+        assert_eq!(uv(0.0, 0.0, 1.0), (0.25, 0.5));
+        assert_eq!(uv(0.0, 0.0, -1.0), (0.75, 0.5));
+        assert_eq!(uv(0.0, 1.0, 0.0), (0.5, 1.0));
+        assert_eq!(uv(0.0, -1.0, 0.0), (0.5, 0.0));
+
+        let mat = Arc::new(Material::lambertian(Color::new(0.5, 0.5, 0.5)));
+        let mut rng = Rng::new(4);
+        for _ in 0..1000 {
+            let center = Vec3::random_range(&mut rng, -10.0, 10.0);
+            let radius = rng.random_f64_range(0.1, 3.0);
+            let sphere = Sphere::new(center, radius, Arc::clone(&mat));
+            for (dir_y, pole_v) in [(-1.0, 1.0), (1.0, 0.0)] {
+                let pos = center - Vec3::new(0.0, 5.0 * dir_y, 0.0);
+                let r = Ray::new(pos, Vec3::new(0.0, dir_y, 0.0));
+                let rec = sphere.hit(&r, 0.001, f64::INFINITY).expect("hit");
+                assert!((0.0..=1.0).contains(&rec.u));
+                assert!((rec.v - pole_v).abs() < 1e-6);
+            }
+        }
     }
 }
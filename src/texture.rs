@@ -0,0 +1,112 @@
+use crate::color::*;
+
+#[derive(Copy, Clone, Debug)]
+pub enum TextureFilter {
+    Nearest,
+    Bilinear,
+}
+
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    texels: Vec<Color>,
+    filter: TextureFilter,
+}
+
+impl ImageTexture {
+    pub fn from_rgba8(width: usize, height: usize, rgba: &[u8], filter: TextureFilter) -> Self {
+        assert!(width > 0 && height > 0);
+        assert_eq!(rgba.len(), 4 * width * height);
+
+        // Texels are sRGB-encoded, so they're decoded to linear values once here.
+        let texels = rgba
+            .chunks_exact(4)
+            .map(|p| Color::from_srgb8(p[0], p[1], p[2]))
+            .collect();
+
+        Self {
+            width,
+            height,
+            texels,
+            filter,
+        }
+    }
+
+    pub fn average(&self) -> Color {
+        1.0 / self.texels.len() as f64 * self.texels.iter().copied().sum::<Color>()
+    }
+
+    fn texel(&self, x: isize, y: isize) -> Color {
+        // Wrap coordinates around so the texture repeats.
+        let x = x.rem_euclid(self.width as isize) as usize;
+        let y = y.rem_euclid(self.height as isize) as usize;
+        self.texels[y * self.width + x]
+    }
+
+    pub fn slope(&self, u: f64, v: f64) -> (f64, f64) {
+        // Rates of change of luminance with `u` and `v`, from the difference between the values
+        // a texel either side, for using the texture as a height map.
+        let du = 1.0 / self.width as f64;
+        let dv = 1.0 / self.height as f64;
+        let height = |u: f64, v: f64| self.value(u, v).luminance();
+        (
+            (height(u + du, v) - height(u - du, v)) / (2.0 * du),
+            (height(u, v + dv) - height(u, v - dv)) / (2.0 * dv),
+        )
+    }
+
+    pub fn value(&self, u: f64, v: f64) -> Color {
+        // Texel centers are at half-integer coordinates; flip v so that v = 1 is the top row.
+        let x = u * self.width as f64 - 0.5;
+        let y = (1.0 - v) * self.height as f64 - 0.5;
+
+        match self.filter {
+            TextureFilter::Nearest => self.texel(x.round() as isize, y.round() as isize),
+            TextureFilter::Bilinear => {
+                let x0 = x.floor();
+                let y0 = y.floor();
+                let fx = x - x0;
+                let fy = y - y0;
+                let (x0, y0) = (x0 as isize, y0 as isize);
+
+                let top = (1.0 - fx) * self.texel(x0, y0) + fx * self.texel(x0 + 1, y0);
+                let bottom = (1.0 - fx) * self.texel(x0, y0 + 1) + fx * self.texel(x0 + 1, y0 + 1);
+                (1.0 - fy) * top + fy * bottom
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bilinear_filtering_blends_neighboring_texels() {
+        // A black texel beside a white one, centered at u = 0.25 and 0.75. Between them, and
+        // where the texture wraps around at u = 0, both count equally.
+        let rgba = [0, 0, 0, 255, 255, 255, 255, 255];
+        let bilinear = ImageTexture::from_rgba8(2, 1, &rgba, TextureFilter::Bilinear);
+        let nearest = ImageTexture::from_rgba8(2, 1, &rgba, TextureFilter::Nearest);
+        for (u, expected) in [
+            (0.25, 0.0),
+            (0.375, 0.25),
+            (0.5, 0.5),
+            (0.75, 1.0),
+            (0.0, 0.5),
+        ] {
+            assert!((bilinear.value(u, 0.5).g() - expected).abs() < 1e-12);
+        }
+        assert_eq!(nearest.value(0.375, 0.5).g(), 0.0);
+        assert_eq!(nearest.value(0.625, 0.5).g(), 1.0);
+
+        // In the middle of four texels, each counts a quarter.
+        let rgba = [
+            0, 0, 0, 255, 255, 255, 255, 255, 255, 0, 0, 255, 0, 255, 0, 255,
+        ];
+        let checker = ImageTexture::from_rgba8(2, 2, &rgba, TextureFilter::Bilinear);
+        let c = checker.value(0.5, 0.5);
+        assert!((c.r() - 0.5).abs() < 1e-12 && (c.g() - 0.5).abs() < 1e-12);
+        assert!((c.b() - 0.25).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,163 @@
+use crate::aabb::*;
+use crate::vec3::*;
+
+// An affine transform: a linear map, given by its rows, followed by an offset.
+#[derive(Copy, Clone, Debug)]
+pub struct Transform {
+    rows: [Vec3; 3],
+    offset: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Self::scale(Vec3::new(1.0, 1.0, 1.0))
+    }
+
+    pub fn translate(offset: Vec3) -> Self {
+        Self {
+            offset,
+            ..Self::identity()
+        }
+    }
+
+    pub fn rotate_y(angle: f64) -> Self {
+        // Turns by `angle` degrees around the Y axis, from +Z towards +X.
+        let (sin, cos) = angle.to_radians().sin_cos();
+        Self {
+            rows: [
+                Vec3::new(cos, 0.0, sin),
+                Vec3::new(0.0, 1.0, 0.0),
+                Vec3::new(-sin, 0.0, cos),
+            ],
+            offset: Vec3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    pub fn scale(s: Vec3) -> Self {
+        Self {
+            rows: [
+                Vec3::new(s.x(), 0.0, 0.0),
+                Vec3::new(0.0, s.y(), 0.0),
+                Vec3::new(0.0, 0.0, s.z()),
+            ],
+            offset: Vec3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    pub fn then(self, next: Self) -> Self {
+        // This transform followed by `next`.
+        let columns = transpose(self.rows);
+        Self {
+            rows: next.rows.map(|row| {
+                Vec3::new(
+                    row.dot(columns[0]),
+                    row.dot(columns[1]),
+                    row.dot(columns[2]),
+                )
+            }),
+            offset: next.point(self.offset),
+        }
+    }
+
+    pub fn inverse(&self) -> Self {
+        // The columns of the inverse of a 3x3 matrix are the cross products of pairs of its rows
+        // over its determinant.
+        let [r0, r1, r2] = self.rows;
+        let det = r0.dot(r1.cross(r2));
+        assert!(det != 0.0, "transform can't be inverted");
+        let rows = transpose([r1.cross(r2) / det, r2.cross(r0) / det, r0.cross(r1) / det]);
+        let inverse = Self {
+            rows,
+            offset: Vec3::new(0.0, 0.0, 0.0),
+        };
+        Self {
+            offset: -inverse.vector(self.offset),
+            ..inverse
+        }
+    }
+
+    pub fn point(&self, p: Vec3) -> Vec3 {
+        self.vector(p) + self.offset
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        // Like `point`, but without the offset, for directions.
+        Vec3::new(
+            self.rows[0].dot(v),
+            self.rows[1].dot(v),
+            self.rows[2].dot(v),
+        )
+    }
+
+    pub fn transpose_vector(&self, v: Vec3) -> Vec3 {
+        // The transpose of `vector`. Normals are transformed by the transpose of the inverse,
+        // so this transforms them back through the transform this is the inverse of.
+        let [r0, r1, r2] = self.rows;
+        v.x() * r0 + v.y() * r1 + v.z() * r2
+    }
+
+    pub fn bounding_box(&self, bbox: Aabb) -> Aabb {
+        // Bounds of the box after transforming, which hold its eight transformed corners.
+        let (min, max) = (bbox.min(), bbox.max());
+        (0..8)
+            .map(|i| {
+                let corner = Vec3::new(
+                    if i & 1 == 0 { min.x() } else { max.x() },
+                    if i & 2 == 0 { min.y() } else { max.y() },
+                    if i & 4 == 0 { min.z() } else { max.z() },
+                );
+                let p = self.point(corner);
+                Aabb::new(p, p)
+            })
+            .fold(Aabb::empty(), Aabb::union)
+    }
+}
+
+fn transpose([r0, r1, r2]: [Vec3; 3]) -> [Vec3; 3] {
+    [
+        Vec3::new(r0.x(), r1.x(), r2.x()),
+        Vec3::new(r0.y(), r1.y(), r2.y()),
+        Vec3::new(r0.z(), r1.z(), r2.z()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).near_zero()
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        // Scaling unevenly, turning and moving, then the inverse, leaves points, directions and
+        // normals where they were, in either order.
+        let t = Transform::scale(Vec3::new(1.0, 2.0, 3.0))
+            .then(Transform::rotate_y(30.0))
+            .then(Transform::translate(Vec3::new(1.0, -2.0, 3.0)));
+        let p = Vec3::new(0.3, -1.2, 2.5);
+        for round_trip in [t.then(t.inverse()), t.inverse().then(t)] {
+            assert!(close(round_trip.point(p), p));
+            assert!(close(round_trip.vector(p), p));
+            assert!(close(round_trip.transpose_vector(p), p));
+        }
+        assert!(close(t.inverse().point(t.point(p)), p));
+
+        // A normal moved through the transpose of the inverse stays perpendicular to the
+        // surface's tangents moved through the transform itself.
+        let normal = Vec3::new(1.0, 1.0, 1.0);
+        let tangents = [Vec3::new(1.0, -1.0, 0.0), Vec3::new(0.0, 1.0, -1.0)];
+        let moved_normal = t.inverse().transpose_vector(normal);
+        for tangent in tangents {
+            assert!(normal.dot(tangent).abs() < 1e-12);
+            assert!(moved_normal.dot(t.vector(tangent)).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use crate::aabb::*;
+use crate::hit_record::*;
+use crate::material::*;
+use crate::ray::*;
+use crate::vec3::*;
+
+use std::sync::Arc;
+
+pub struct TriangleMesh {
+    positions: Arc<Vec<Vec3>>,
+    normals: Option<Arc<Vec<Vec3>>>, // Per-vertex normals for smooth shading
+    uvs: Option<Arc<Vec<(f64, f64)>>>, // Per-vertex texture coordinates
+    indices: Vec<[usize; 3]>,
+    mat: Arc<Material>,
+}
+
+impl TriangleMesh {
+    pub fn new(positions: Arc<Vec<Vec3>>, indices: Vec<[usize; 3]>, mat: Arc<Material>) -> Self {
+        assert!(indices.iter().flatten().all(|&i| i < positions.len()));
+
+        Self {
+            positions,
+            normals: None,
+            uvs: None,
+            indices,
+            mat,
+        }
+    }
+
+    pub fn smooth(
+        positions: Arc<Vec<Vec3>>,
+        normals: Arc<Vec<Vec3>>,
+        indices: Vec<[usize; 3]>,
+        mat: Arc<Material>,
+    ) -> Self {
+        assert_eq!(positions.len(), normals.len());
+
+        Self {
+            normals: Some(normals),
+            ..Self::new(positions, indices, mat)
+        }
+    }
+
+    pub fn with_uvs(self, uvs: Arc<Vec<(f64, f64)>>) -> Self {
+        // Textures the mesh with a `u`, `v` for each vertex; without them every hit is at 0, 0.
+        // Nothing loads these from files yet: an OBJ loader would fill them from `vt` lines
+        // and `v/vt/vn` faces.
+        assert_eq!(self.positions.len(), uvs.len());
+
+        Self {
+            uvs: Some(uvs),
+            ..self
+        }
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        self.indices
+            .iter()
+            .flatten()
+            .map(|&i| Aabb::new(self.positions[i], self.positions[i]))
+            .fold(Aabb::empty(), Aabb::union)
+    }
+
+    fn hit_triangle(
+        &self,
+        r: &Ray,
+        tri: [usize; 3],
+        ray_tmin: f64,
+        ray_tmax: f64,
+    ) -> Option<(f64, f64, f64)> {
+        // Returns the ray's `t` and the barycentric weights of the second and third vertices.
+        // Möller-Trumbore ray-triangle intersection.
+        let p0 = self.positions[tri[0]];
+        let edge1 = self.positions[tri[1]] - p0;
+        let edge2 = self.positions[tri[2]] - p0;
+
+        let pvec = r.dir.cross(edge2);
+        let det = edge1.dot(pvec);
+        if det.abs() < 1.0e-12 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let tvec = r.pos - p0;
+        let b1 = tvec.dot(pvec) * inv_det;
+        if !(0.0..=1.0).contains(&b1) {
+            return None;
+        }
+
+        let qvec = tvec.cross(edge1);
+        let b2 = r.dir.dot(qvec) * inv_det;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return None;
+        }
+
+        let t = edge2.dot(qvec) * inv_det;
+        if t <= ray_tmin || t >= ray_tmax {
+            return None;
+        }
+
+        Some((t, b1, b2))
+    }
+
+    pub fn hit<'s>(&'s self, r: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord<'s>> {
+        let mut closest: Option<(f64, f64, f64, [usize; 3])> = None;
+        let mut closest_so_far = ray_tmax;
+
+        for &tri in &self.indices {
+            if let Some((t, b1, b2)) = self.hit_triangle(r, tri, ray_tmin, closest_so_far) {
+                closest = Some((t, b1, b2, tri));
+                closest_so_far = t;
+            }
+        }
+
+        closest.map(|(t, b1, b2, tri)| {
+            let p0 = self.positions[tri[0]];
+            let edge1 = self.positions[tri[1]] - p0;
+            let edge2 = self.positions[tri[2]] - p0;
+            let outward_normal = edge1.cross(edge2).unit();
+
+            let b0 = 1.0 - b1 - b2;
+            let uv = self.uvs.as_ref().map_or((0.0, 0.0), |uvs| {
+                let (uv0, uv1, uv2) = (uvs[tri[0]], uvs[tri[1]], uvs[tri[2]]);
+                (
+                    b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0,
+                    b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1,
+                )
+            });
+
+            let mut rec = HitRecord::new(r, t, outward_normal, uv, &self.mat);
+
+            // With texture coordinates, the edges' changes in `p` and in `u`, `v` give the rates
+            // of change of `p` with `u` and `v` across the triangle.
+            if let Some(uvs) = &self.uvs {
+                let (uv0, uv1, uv2) = (uvs[tri[0]], uvs[tri[1]], uvs[tri[2]]);
+                let (du1, dv1) = (uv1.0 - uv0.0, uv1.1 - uv0.1);
+                let (du2, dv2) = (uv2.0 - uv0.0, uv2.1 - uv0.1);
+                let det = du1 * dv2 - du2 * dv1;
+                if det.abs() > 1.0e-12 {
+                    rec = rec.with_derivatives(
+                        (dv2 * edge1 - dv1 * edge2) / det,
+                        (du1 * edge2 - du2 * edge1) / det,
+                    );
+                }
+            }
+
+            // Shade with interpolated vertex normals if there are any, but keep the face
+            // orientation from the geometric normal.
+            if let Some(normals) = &self.normals {
+                let shading_normal =
+                    (b0 * normals[tri[0]] + b1 * normals[tri[1]] + b2 * normals[tri[2]]).unit();
+                rec.normal = if rec.front_face {
+                    shading_normal
+                } else {
+                    -shading_normal
+                };
+            }
+
+            rec
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::*;
+
+    fn unit_quad() -> TriangleMesh {
+        // Two triangles covering x, y from 0 to 1 at z = 0, facing +Z.
+        let positions = vec![
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ];
+        let mat = Arc::new(Material::lambertian(Color::new(0.5, 0.5, 0.5)));
+        TriangleMesh::new(Arc::new(positions), vec![[0, 1, 2], [0, 2, 3]], mat)
+    }
+
+    fn hit_at(mesh: &TriangleMesh, x: f64, y: f64) -> Option<HitRecord<'_>> {
+        let r = Ray::new(Vec3::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        mesh.hit(&r, 0.001, f64::INFINITY)
+    }
+
+    #[test]
+    fn uvs_are_interpolated_across_faces() {
+        // With each corner's `u`, `v` the same as its x, y, every hit is at its own x, y, even
+        // on the edge the triangles share.
+        let uvs = vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let mesh = unit_quad().with_uvs(Arc::new(uvs));
+        for (x, y) in [(0.5, 0.5), (0.75, 0.25), (0.25, 0.75), (0.1, 0.9)] {
+            let rec = hit_at(&mesh, x, y).expect("hit");
+            assert!((rec.u - x).abs() < 1e-12 && (rec.v - y).abs() < 1e-12);
+        }
+
+        let untextured = unit_quad();
+        let rec = hit_at(&untextured, 0.5, 0.5).expect("hit");
+        assert_eq!((rec.u, rec.v), (0.0, 0.0));
+    }
+
+    #[test]
+    fn quad_mesh_hits_inside_and_misses_outside() {
+        // Hits inside either triangle are on the plane facing +Z, from either side; points
+        // outside the quad, or rays along its plane, miss.
+        let mesh = unit_quad();
+        for (x, y) in [(0.25, 0.75), (0.75, 0.25), (0.5, 0.5), (0.0, 0.0)] {
+            let rec = hit_at(&mesh, x, y).expect("hit");
+            assert!((rec.t - 1.0).abs() < 1e-12);
+            assert!((rec.p - Vec3::new(x, y, 0.0)).near_zero());
+            assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).near_zero());
+            assert!(rec.front_face);
+        }
+        let from_below = Ray::new(Vec3::new(0.5, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let rec = mesh.hit(&from_below, 0.001, f64::INFINITY).expect("hit");
+        assert!((rec.normal - Vec3::new(0.0, 0.0, -1.0)).near_zero());
+        assert!(!rec.front_face);
+
+        for (x, y) in [(-0.1, 0.5), (1.1, 0.5), (0.5, 1.1), (0.5, -0.1)] {
+            assert!(hit_at(&mesh, x, y).is_none());
+        }
+        let along = Ray::new(Vec3::new(-1.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(mesh.hit(&along, 0.001, f64::INFINITY).is_none());
+
+        // Nor is the quad hit beyond `ray_tmax`.
+        let r = Ray::new(Vec3::new(0.5, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(mesh.hit(&r, 0.001, 0.5).is_none());
+    }
+
+    #[test]
+    fn smooth_normals_vary_but_faces_do_not() {
+        // Vertex normals leaning out from the middle, like a dome, tilt the normal toward each
+        // hit's own corner, while which side was hit still comes from the flat face.
+        let positions = vec![
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ];
+        let normals = positions
+            .iter()
+            .map(|&p| (p - Vec3::new(0.5, 0.5, 0.0) + Vec3::new(0.0, 0.0, 1.0)).unit())
+            .collect();
+        let mat = Arc::new(Material::lambertian(Color::new(0.5, 0.5, 0.5)));
+        let mesh = TriangleMesh::smooth(
+            Arc::new(positions),
+            Arc::new(normals),
+            vec![[0, 1, 2], [0, 2, 3]],
+            mat,
+        );
+
+        let center = hit_at(&mesh, 0.5, 0.5).expect("hit");
+        assert!((center.normal - Vec3::new(0.0, 0.0, 1.0)).near_zero());
+        for (x, y) in [(0.2, 0.3), (0.8, 0.3), (0.7, 0.9), (0.1, 0.6)] {
+            let rec = hit_at(&mesh, x, y).expect("hit");
+            assert!((rec.normal.length() - 1.0).abs() < 1e-12);
+            assert!(rec.normal.x() * (x - 0.5) > 0.0 && rec.normal.y() * (y - 0.5) > 0.0);
+            assert!(rec.front_face);
+
+            let from_below = Ray::new(Vec3::new(x, y, -1.0), Vec3::new(0.0, 0.0, 1.0));
+            let below = mesh.hit(&from_below, 0.001, f64::INFINITY).expect("hit");
+            assert!((below.normal + rec.normal).near_zero());
+            assert!(!below.front_face);
+        }
+    }
+}
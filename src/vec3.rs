@@ -1,39 +1,86 @@
 use crate::random::*;
 
-#[derive(Copy, Clone, Debug)]
-pub struct Vec3([f64; 3]);
+#[cfg(not(feature = "simd"))]
+const LANES: usize = 3;
+
+// Padding to four lanes lets whole-vector arithmetic compile down to 256-bit SIMD instructions.
+// The padding lane is always zero on construction, but isn't relied upon by anything.
+#[cfg(feature = "simd")]
+const LANES: usize = 4;
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "simd", repr(align(32)))]
+pub struct Vec3([f64; LANES]);
 
 impl Vec3 {
+    #[inline]
     pub fn new(x: f64, y: f64, z: f64) -> Self {
-        Self([x, y, z])
+        let mut lanes = [0.0; LANES];
+        lanes[..3].copy_from_slice(&[x, y, z]);
+        Self(lanes)
+    }
+
+    #[inline]
+    fn map2(self, rhs: Self, f: impl Fn(f64, f64) -> f64) -> Self {
+        Self(std::array::from_fn(|i| f(self.0[i], rhs.0[i])))
     }
 
+    pub fn from_spherical(theta: f64, phi: f64) -> Self {
+        // Unit vector with polar angle `theta` from +Y, and azimuth `phi` around Y from +X
+        // towards +Z.
+        let sin_theta = theta.sin();
+        Self::new(sin_theta * phi.cos(), theta.cos(), sin_theta * phi.sin())
+    }
+
+    pub fn to_spherical(&self) -> (f64, f64) {
+        // Inverse of `from_spherical`: returns `(theta, phi)` with theta in [0,pi] and phi in
+        // [0,2pi). The vector's length is ignored.
+        let theta = (self.0[1] / self.length()).clamp(-1.0, 1.0).acos();
+        let two_pi = 2.0 * std::f64::consts::PI;
+        let phi = self.0[2].atan2(self.0[0]).rem_euclid(two_pi);
+
+        // Tiny negative angles round up to 2pi itself when wrapped, which is the same as zero.
+        let phi = if phi < two_pi { phi } else { 0.0 };
+        (theta, phi)
+    }
+
+    #[inline]
     pub fn x(&self) -> f64 {
         self.0[0]
     }
 
+    #[inline]
     pub fn y(&self) -> f64 {
         self.0[1]
     }
 
+    #[inline]
     pub fn z(&self) -> f64 {
         self.0[2]
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = f64> + '_ {
+        self.0[..3].iter().copied()
+    }
+
+    #[inline]
     pub fn dot(self, other: Self) -> f64 {
-        self.0[0] * other.0[0] + self.0[1] * other.0[1] + self.0[2] * other.0[2]
+        let p = self * other;
+        p.0[0] + p.0[1] + p.0[2]
     }
 
+    #[inline]
     pub fn cross(self, other: Self) -> Self {
-        Self([
+        Self::new(
             self.0[1] * other.0[2] - self.0[2] * other.0[1],
             self.0[2] * other.0[0] - self.0[0] * other.0[2],
             self.0[0] * other.0[1] - self.0[1] * other.0[0],
-        ])
+        )
     }
 
+    #[inline]
     pub fn length_squared(&self) -> f64 {
-        self.0[0] * self.0[0] + self.0[1] * self.0[1] + self.0[2] * self.0[2]
+        self.dot(*self)
     }
 
     pub fn near_zero(&self) -> bool {
@@ -43,32 +90,34 @@ impl Vec3 {
     }
 
     pub fn random(rng: &mut Rng) -> Self {
-        Self([rng.random_f64(), rng.random_f64(), rng.random_f64()])
+        Self::new(rng.random_f64(), rng.random_f64(), rng.random_f64())
     }
 
     pub fn random_range(rng: &mut Rng, min: f64, max: f64) -> Self {
-        Self([
+        Self::new(
             rng.random_f64_range(min, max),
             rng.random_f64_range(min, max),
             rng.random_f64_range(min, max),
-        ])
+        )
     }
 
+    #[inline]
     pub fn length(&self) -> f64 {
         self.length_squared().sqrt()
     }
 
+    #[inline]
     pub fn unit(&self) -> Self {
         *self / self.length()
     }
 
     pub fn random_in_unit_disk(rng: &mut Rng) -> Self {
         loop {
-            let p = Self([
+            let p = Self::new(
                 rng.random_f64_range(-1.0, 1.0),
                 rng.random_f64_range(-1.0, 1.0),
                 0.0,
-            ]);
+            );
             if p.length_squared() < 1.0 {
                 return p;
             }
@@ -76,10 +125,13 @@ impl Vec3 {
     }
 
     pub fn random_unit_vector(rng: &mut Rng) -> Self {
+        // Only points within the unit sphere are uniformly distributed in direction; the corners
+        // of the cube would favor its diagonals. Lambertian scattering and the light sampling
+        // weights rely on `normal + random_unit_vector` having a density of `cos_theta / pi`.
         loop {
             let p = Self::random_range(rng, -1.0, 1.0);
             let lensq = p.length_squared();
-            if lensq > 1.0e-160 {
+            if 1.0e-160 < lensq && lensq <= 1.0 {
                 return p / lensq.sqrt();
             }
         }
@@ -95,84 +147,237 @@ impl Vec3 {
         let r_out_parallel = -(1.0 - r_out_perp.length_squared()).abs().sqrt() * n;
         r_out_perp + r_out_parallel
     }
+
+    pub fn refract_checked(self, n: Self, etai_over_etat: f64) -> Option<Self> {
+        // Like `refract`, but `None` if there's total internal reflection instead.
+        let cos_theta = f64::min((-self).dot(n), 1.0);
+        let r_out_perp = etai_over_etat * (self + cos_theta * n);
+        let discriminant = 1.0 - r_out_perp.length_squared();
+        if discriminant < 0.0 {
+            return None;
+        }
+        Some(r_out_perp - discriminant.sqrt() * n)
+    }
+}
+
+impl std::fmt::Debug for Vec3 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Vec3")
+            .field(&<[f64; 3]>::from(*self))
+            .finish()
+    }
+}
+
+impl From<[f64; 3]> for Vec3 {
+    fn from(a: [f64; 3]) -> Vec3 {
+        Vec3::new(a[0], a[1], a[2])
+    }
+}
+
+impl From<Vec3> for [f64; 3] {
+    fn from(v: Vec3) -> [f64; 3] {
+        [v.0[0], v.0[1], v.0[2]]
+    }
 }
 
 impl std::ops::Add for Vec3 {
     type Output = Vec3;
 
+    #[inline]
     fn add(self, rhs: Vec3) -> Vec3 {
-        Vec3([
-            self.0[0] + rhs.0[0],
-            self.0[1] + rhs.0[1],
-            self.0[2] + rhs.0[2],
-        ])
+        self.map2(rhs, |a, b| a + b)
     }
 }
 
 impl std::ops::Sub for Vec3 {
     type Output = Vec3;
 
+    #[inline]
     fn sub(self, rhs: Vec3) -> Vec3 {
-        Vec3([
-            self.0[0] - rhs.0[0],
-            self.0[1] - rhs.0[1],
-            self.0[2] - rhs.0[2],
-        ])
+        self.map2(rhs, |a, b| a - b)
     }
 }
 
 impl std::ops::Mul for Vec3 {
     type Output = Vec3;
 
+    #[inline]
     fn mul(self, rhs: Vec3) -> Vec3 {
-        Vec3([
-            self.0[0] * rhs.0[0],
-            self.0[1] * rhs.0[1],
-            self.0[2] * rhs.0[2],
-        ])
+        self.map2(rhs, |a, b| a * b)
     }
 }
 
 impl std::ops::Mul<Vec3> for f64 {
     type Output = Vec3;
 
+    #[inline]
     fn mul(self, rhs: Vec3) -> Vec3 {
-        Vec3([self * rhs.0[0], self * rhs.0[1], self * rhs.0[2]])
+        Vec3(rhs.0.map(|a| self * a))
     }
 }
 
 impl std::ops::Div for Vec3 {
     type Output = Vec3;
 
+    #[inline]
     fn div(self, rhs: Vec3) -> Vec3 {
-        Vec3([
-            self.0[0] / rhs.0[0],
-            self.0[1] / rhs.0[1],
-            self.0[2] / rhs.0[2],
-        ])
+        self.map2(rhs, |a, b| a / b)
     }
 }
 
 impl std::ops::Div<f64> for Vec3 {
     type Output = Vec3;
 
+    #[inline]
     fn div(self, rhs: f64) -> Vec3 {
-        Vec3([self.0[0] / rhs, self.0[1] / rhs, self.0[2] / rhs])
+        Vec3(self.0.map(|a| a / rhs))
     }
 }
 
 impl std::ops::Neg for Vec3 {
     type Output = Vec3;
 
+    #[inline]
     fn neg(self) -> Vec3 {
-        Vec3([-self.0[0], -self.0[1], -self.0[2]])
+        Vec3(self.0.map(|a| -a))
     }
 }
 
 impl std::ops::AddAssign for Vec3 {
+    #[inline]
     fn add_assign(&mut self, rhs: Vec3) {
-        self.0[0] += rhs.0[0];
-        self.0[1] += rhs.0[1];
-        self.0[2] += rhs.0[2];
+        *self = *self + rhs;
+    }
+}
+
+impl std::iter::Sum for Vec3 {
+    fn sum<I: Iterator<Item = Vec3>>(iter: I) -> Vec3 {
+        iter.fold(Vec3::new(0.0, 0.0, 0.0), |acc, v| acc + v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_unit_vector_is_uniform() {
+        // Uniform directions have each coordinate uniform in [-1,1], so `|z| > a` for a
+        // fraction `1 - a` of them; directions normalized from the cube fall short near the axes.
+        let mut rng = Rng::new(1);
+        let n = 200_000;
+        for a in [0.5, 0.9, 0.99] {
+            let count = (0..n)
+                .filter(|_| Vec3::random_unit_vector(&mut rng).z().abs() > a)
+                .count();
+            let expected = 1.0 - a;
+            assert!((count as f64 / n as f64 - expected).abs() < 0.1 * expected);
+        }
+    }
+
+    #[test]
+    fn ops_match_scalar_math() {
+        // Every operation gives exactly what plain arithmetic on three `f64`s does, whether or
+        // not the `simd` feature pads the vectors to four lanes.
+        let mut rng = Rng::new(2);
+        let arr = |v: Vec3| <[f64; 3]>::from(v);
+        let zip = |a: [f64; 3], b: [f64; 3], f: fn(f64, f64) -> f64| {
+            [f(a[0], b[0]), f(a[1], b[1]), f(a[2], b[2])]
+        };
+
+        for _ in 0..10_000 {
+            let (u, v) = (
+                Vec3::random_range(&mut rng, -100.0, 100.0),
+                Vec3::random_range(&mut rng, -100.0, 100.0),
+            );
+            let k = rng.random_f64_range(-10.0, 10.0);
+            let (a, b) = (arr(u), arr(v));
+
+            assert_eq!(arr(u + v), zip(a, b, |x, y| x + y));
+            assert_eq!(arr(u - v), zip(a, b, |x, y| x - y));
+            assert_eq!(arr(u * v), zip(a, b, |x, y| x * y));
+            assert_eq!(arr(u / v), zip(a, b, |x, y| x / y));
+            assert_eq!(arr(k * u), a.map(|x| k * x));
+            assert_eq!(arr(u / k), a.map(|x| x / k));
+            assert_eq!(arr(-u), a.map(|x| -x));
+
+            let dot = a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+            assert_eq!(u.dot(v), dot);
+            assert_eq!(u.length_squared(), a[0] * a[0] + a[1] * a[1] + a[2] * a[2]);
+            assert_eq!(u.length(), u.length_squared().sqrt());
+            assert_eq!(arr(u.unit()), a.map(|x| x / u.length()));
+            assert_eq!(
+                arr(u.cross(v)),
+                [
+                    a[1] * b[2] - a[2] * b[1],
+                    a[2] * b[0] - a[0] * b[2],
+                    a[0] * b[1] - a[1] * b[0],
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn arrays_round_trip() {
+        // Converting to and from `[f64; 3]` keeps every coordinate, in order, exactly.
+        let a = [1.5, -2.0, 1e-300];
+        let v = Vec3::from(a);
+        assert_eq!((v.x(), v.y(), v.z()), (1.5, -2.0, 1e-300));
+        assert_eq!(<[f64; 3]>::from(v), a);
+
+        let mut rng = Rng::new(3);
+        for _ in 0..1000 {
+            let v = Vec3::random_range(&mut rng, -100.0, 100.0);
+            let round_trip = Vec3::from(<[f64; 3]>::from(v));
+            assert_eq!(<[f64; 3]>::from(round_trip), <[f64; 3]>::from(v));
+        }
+    }
+
+    #[test]
+    fn refract_checked_reflects_past_the_critical_angle() {
+        // Leaving glass, light refracts by Snell's law up to about 41.8 degrees from the normal
+        // and is all reflected beyond it. Entering glass, it always refracts.
+        let n = Vec3::new(0.0, 0.0, 1.0);
+        let incoming = |degrees: f64| {
+            let (sin, cos) = degrees.to_radians().sin_cos();
+            Vec3::new(sin, 0.0, -cos)
+        };
+        let critical = (1.0 / 1.5_f64).asin().to_degrees();
+        for degrees in [0.0, 20.0, critical - 0.1] {
+            let dir = incoming(degrees);
+            let out = dir.refract_checked(n, 1.5).expect("refract_checked");
+            assert!((out.x() - 1.5 * dir.x()).abs() < 1e-12);
+            assert!((out.length() - 1.0).abs() < 1e-12 && out.z() < 0.0);
+            assert!((out - dir.refract(n, 1.5)).near_zero());
+        }
+        for degrees in [critical + 0.1, 60.0, 89.0] {
+            assert!(incoming(degrees).refract_checked(n, 1.5).is_none());
+            assert!(incoming(degrees).refract_checked(n, 1.0 / 1.5).is_some());
+        }
+    }
+
+    #[test]
+    fn spherical_round_trip() {
+        // Angles come back as they went in, including at the poles' ends of `theta` and just
+        // either side of where `phi` wraps around, and lengths don't matter.
+        let two_pi = 2.0 * std::f64::consts::PI;
+        for theta in [0.3, 1.0, 2.5] {
+            for phi in [0.0, 1e-9, 1.0, 4.0, two_pi - 1e-9] {
+                let v = Vec3::from_spherical(theta, phi);
+                assert!((v.length() - 1.0).abs() < 1e-12);
+                let (t, p) = (3.0 * v).to_spherical();
+                assert!((t - theta).abs() < 1e-12 && (p - phi).abs() < 1e-12);
+            }
+        }
+        for (theta, y) in [(0.0, 1.0), (std::f64::consts::PI, -1.0)] {
+            let (t, _) = Vec3::new(0.0, y, 0.0).to_spherical();
+            assert_eq!(t, theta);
+        }
+
+        // A hair below +X is at the very end of the range of `phi`, but still inside it.
+        for z in [-1e-300, -1e-17, 0.0, 1e-17] {
+            let (_, phi) = Vec3::new(1.0, 0.0, z).to_spherical();
+            assert!((0.0..two_pi).contains(&phi));
+        }
     }
 }
@@ -0,0 +1,99 @@
+// Helpers shared by the integration tests.
+
+use std::io::{Error, ErrorKind};
+
+fn invalid(what: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("reference PNG: {what}"))
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+pub fn read_png_rgba8(path: &str) -> std::io::Result<(u32, u32, Vec<u8>)> {
+    // Reads an 8-bit RGBA PNG as written by `png::write_rgba8`, with stored (uncompressed)
+    // deflate blocks and no scanline filtering, which is all references need to be.
+    let bytes = std::fs::read(path)?;
+    let mut chunks = bytes
+        .strip_prefix(b"\x89PNG\r\n\x1a\n")
+        .ok_or_else(|| invalid("bad signature"))?;
+
+    let mut size = None;
+    let mut idat = vec![];
+    while chunks.len() >= 12 {
+        let len = be_u32(&chunks[0..4]) as usize;
+        let kind = &chunks[4..8];
+        let data = chunks
+            .get(8..8 + len)
+            .ok_or_else(|| invalid("truncated chunk"))?;
+        match kind {
+            b"IHDR" => {
+                if data.len() != 13 || data[8..13] != [8, 6, 0, 0, 0] {
+                    return Err(invalid("not 8-bit RGBA"));
+                }
+                size = Some((be_u32(&data[0..4]), be_u32(&data[4..8])));
+            }
+            b"IDAT" => idat.extend_from_slice(data),
+            _ => {}
+        }
+        chunks = &chunks[12 + len..];
+    }
+    let (width, height) = size.ok_or_else(|| invalid("no IHDR"))?;
+
+    // Skip the zlib header, then copy out each stored block until the final one.
+    let mut raw = vec![];
+    let mut blocks = idat.get(2..).ok_or_else(|| invalid("no IDAT"))?;
+    loop {
+        let [header, len_lo, len_hi, ..] = *blocks else {
+            return Err(invalid("truncated IDAT"));
+        };
+        if header & 0b110 != 0 {
+            return Err(invalid("compressed deflate block"));
+        }
+        let len = u16::from_le_bytes([len_lo, len_hi]) as usize;
+        let block = blocks
+            .get(5..5 + len)
+            .ok_or_else(|| invalid("truncated IDAT"))?;
+        raw.extend_from_slice(block);
+        blocks = &blocks[5 + len..];
+        if header & 1 != 0 {
+            break;
+        }
+    }
+
+    let row_len = 4 * width as usize;
+    if raw.len() != (row_len + 1) * height as usize {
+        return Err(invalid("wrong amount of image data"));
+    }
+    let mut rgba = Vec::with_capacity(row_len * height as usize);
+    for row in raw.chunks_exact(row_len + 1) {
+        if row[0] != 0 {
+            return Err(invalid("filtered scanline"));
+        }
+        rgba.extend_from_slice(&row[1..]);
+    }
+    Ok((width, height, rgba))
+}
+
+pub fn assert_image_matches(rendered: &[u8], reference_path: &str, max_mae: f64) {
+    // Compares RGBA bytes with a reference PNG by their mean absolute error, in 8-bit steps over
+    // every channel, so small differences in floating point from one machine to another pass.
+    let (width, height, reference) =
+        read_png_rgba8(reference_path).unwrap_or_else(|e| panic!("{reference_path}: {e}"));
+    assert_eq!(
+        rendered.len(),
+        reference.len(),
+        "rendered image isn't the {width}x{height} of {reference_path}"
+    );
+
+    let total_error: u64 = rendered
+        .iter()
+        .zip(&reference)
+        .map(|(&a, &b)| a.abs_diff(b) as u64)
+        .sum();
+    let mae = total_error as f64 / rendered.len() as f64;
+    assert!(
+        mae <= max_mae,
+        "mean absolute error {mae:.3} from {reference_path} is over {max_mae}"
+    );
+}
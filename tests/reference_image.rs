@@ -0,0 +1,54 @@
+mod common;
+
+use common::*;
+use raytracing::camera::*;
+use raytracing::random::*;
+use raytracing::scene::*;
+
+use std::sync::Arc;
+
+const DEMO_REFERENCE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/reference/demo_64x36.png"
+);
+const MANY_SAMPLES_REFERENCE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/reference/demo_32x18_256.png"
+);
+
+#[test]
+fn demo_matches_reference() {
+    // The demo scene at 64x36 with fixed seeds and 16 samples per pixel. Run with
+    // `UPDATE_REFERENCE_IMAGES=1` to replace the reference after an intended change in output.
+    let (scene, camera_options) = random_spheres(&mut Rng::new(0));
+    let camera_options = CameraOptions {
+        image_width: 64,
+        ..camera_options
+    };
+    let mut camera = Camera::new(&Arc::new(scene), 1, 4, camera_options);
+    camera.render_to_completion(16);
+
+    if std::env::var_os("UPDATE_REFERENCE_IMAGES").is_some() {
+        camera.save_png(DEMO_REFERENCE).expect("save_png");
+    }
+    assert_image_matches(&camera.frame_rgba8(), DEMO_REFERENCE, 0.5);
+}
+
+#[test]
+fn many_samples_match_reference() {
+    // Like `demo_matches_reference`, but at 32x18 with 256 samples per pixel, enough for rounding
+    // in the sums of samples to build up. The reference is rendered without `f32-accum`, so
+    // running this with `--features f32-accum` checks single precision stays close to double.
+    let (scene, camera_options) = random_spheres(&mut Rng::new(0));
+    let camera_options = CameraOptions {
+        image_width: 32,
+        ..camera_options
+    };
+    let mut camera = Camera::new(&Arc::new(scene), 1, 4, camera_options);
+    camera.render_to_completion(256);
+
+    if std::env::var_os("UPDATE_REFERENCE_IMAGES").is_some() {
+        camera.save_png(MANY_SAMPLES_REFERENCE).expect("save_png");
+    }
+    assert_image_matches(&camera.frame_rgba8(), MANY_SAMPLES_REFERENCE, 0.1);
+}